@@ -0,0 +1,92 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{device_id::F1Variant, dirs, usb_ids::KnownProbe};
+
+const TEST_BIN_FOLDER: &str = "test bin";
+
+/// Everything needed to flash one kind of board.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BoardProfile {
+    pub name: String,
+    pub bootloader_path: PathBuf,
+    pub firmware_path: PathBuf,
+    pub user_file_path: PathBuf,
+    pub target_name: String,
+    pub target_waiting_time: u64,
+    #[serde(default)]
+    pub probe: KnownProbe,
+    /// Interface MCU of the board, used until the chip is identified.
+    #[serde(default)]
+    pub host_family: F1Variant,
+}
+
+impl BoardProfile {
+    fn bundled(name: &str, target_name: &str, test_file: &str) -> Self {
+        let user_file_path = dirs::get_exe_dir()
+            .map(|dir| dir.join(TEST_BIN_FOLDER).join(test_file))
+            .unwrap_or_default();
+
+        Self {
+            name: name.into(),
+            bootloader_path: PathBuf::default(),
+            firmware_path: PathBuf::default(),
+            user_file_path,
+            target_name: target_name.into(),
+            target_waiting_time: 10,
+            probe: KnownProbe::StLink,
+            host_family: F1Variant::Stm32,
+        }
+    }
+}
+
+/// The boards documented in the README, with the matching test program of
+/// `test bin`. The bootloader and firmware are downloaded by the user.
+pub fn default_profiles() -> Vec<BoardProfile> {
+    vec![
+        BoardProfile::bundled("STeaMi", "STEAMI", "test-wb55.bin"),
+        BoardProfile::bundled("Disco L475 IoT Node", "DIS_L4IOT", "test-l475.bin"),
+        BoardProfile::bundled("Nucleo WB55", "DAPLINK", "test-wb55.bin"),
+    ]
+}
+
+/// `name` if no profile uses it yet, otherwise `name (2)`, `name (3)`...
+pub fn unique_name(profiles: &[BoardProfile], name: &str) -> String {
+    let is_used = |candidate: &str| profiles.iter().any(|p| p.name == candidate);
+
+    if !is_used(name) {
+        return name.to_string();
+    }
+
+    (2..)
+        .map(|i| format!("{name} ({i})"))
+        .find(|candidate| !is_used(candidate))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unique_name_adds_a_counter() {
+        let profiles = default_profiles();
+
+        assert_eq!(unique_name(&profiles, "Custom"), "Custom");
+        assert_eq!(unique_name(&profiles, "STeaMi"), "STeaMi (2)");
+    }
+
+    #[test]
+    fn profiles_without_probe_load_with_defaults() {
+        let json = r#"{"name":"Old","bootloader_path":"","firmware_path":"","user_file_path":"","target_name":"STEAMI","target_waiting_time":10}"#;
+        let profile: BoardProfile = serde_json::from_str(json).unwrap();
+        assert_eq!(profile.probe, KnownProbe::StLink);
+        assert_eq!(profile.host_family, F1Variant::Stm32);
+
+        let json = json.replace('}', r#","probe":"CmsisDap","host_family":"Stm32F103xB"}"#);
+        let profile: BoardProfile = serde_json::from_str(&json).unwrap();
+        assert_eq!(profile.probe, KnownProbe::CmsisDap);
+        assert_eq!(profile.host_family, F1Variant::Stm32);
+    }
+}
@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::usb_ids::KnownProbe;
+
+/// Debug MCU identity register, at the same address on every Cortex-M3/M4
+/// STM32.
+pub const DBGMCU_IDCODE: u32 = 0xE004_2000;
+
+/// STM32 families we talk to over SWD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwdTarget {
+    /// The DAPLink interface chip.
+    Stm32F1,
+    Stm32Wb,
+}
+
+pub const ALL_SWD_TARGETS: [SwdTarget; 2] = [SwdTarget::Stm32F1, SwdTarget::Stm32Wb];
+
+impl std::fmt::Display for SwdTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SwdTarget::Stm32F1 => "Interface MCU (STM32F1, ST-Link)",
+            SwdTarget::Stm32Wb => "STM32WB55 (CMSIS-DAP)",
+        })
+    }
+}
+
+impl SwdTarget {
+    /// Address of the 96 bits unique device ID.
+    pub fn uid_address(&self) -> u32 {
+        match self {
+            SwdTarget::Stm32F1 => 0x1FFF_F7E8,
+            SwdTarget::Stm32Wb => 0x1FFF_7590,
+        }
+    }
+
+    /// Address of the flash size (KiB, 16 bits).
+    pub fn flash_size_address(&self) -> u32 {
+        match self {
+            SwdTarget::Stm32F1 => 0x1FFF_F7E0,
+            SwdTarget::Stm32Wb => 0x1FFF_75E0,
+        }
+    }
+
+    /// OpenOCD commands dumping the three identification registers.
+    pub fn read_commands(&self) -> Vec<String> {
+        vec![
+            "scan_chain".into(),
+            format!("mdw 0x{:08X} 3", self.uid_address()),
+            format!("mdh 0x{:08X}", self.flash_size_address()),
+            format!("mdw 0x{DBGMCU_IDCODE:08X}"),
+        ]
+    }
+}
+
+/// Identification of an STM32 read over SWD.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceId {
+    /// Unique ID words, lowest address first.
+    pub uid: [u32; 3],
+    pub flash_size_kb: u16,
+    pub idcode: u32,
+    /// SW-DP IDCODE, from the `scan_chain` table.
+    pub dp_idcode: Option<u32>,
+}
+
+impl DeviceId {
+    /// Parses the output of `read_commands`: OpenOCD prints memory dumps as
+    /// `0x1ffff7e8: 066dff49 48527751 87223342`.
+    pub fn parse<'a>(
+        target: SwdTarget,
+        lines: impl IntoIterator<Item = &'a str>,
+    ) -> Result<DeviceId, String> {
+        let lines: Vec<&str> = lines.into_iter().collect();
+        let dumps = parse_memory_dumps(lines.iter().copied());
+        let word = |address: u32, index: usize| -> Result<u32, String> {
+            dumps
+                .get(&address)
+                .and_then(|values| values.get(index))
+                .copied()
+                .ok_or_else(|| format!("0x{address:08X} was not read"))
+        };
+
+        let uid_address = target.uid_address();
+        Ok(DeviceId {
+            uid: [
+                word(uid_address, 0)?,
+                word(uid_address, 1)?,
+                word(uid_address, 2)?,
+            ],
+            flash_size_kb: word(target.flash_size_address(), 0)? as u16,
+            idcode: word(DBGMCU_IDCODE, 0)?,
+            dp_idcode: parse_tap_idcode(&lines),
+        })
+    }
+
+    /// Most significant word first, as ST tools show it.
+    pub fn uid_hex(&self) -> String {
+        format!("{:08X}{:08X}{:08X}", self.uid[2], self.uid[1], self.uid[0])
+    }
+
+    pub fn dev_id(&self) -> u16 {
+        (self.idcode & 0xFFF) as u16
+    }
+
+    pub fn rev_id(&self) -> u16 {
+        (self.idcode >> 16) as u16
+    }
+
+    pub fn device_name(&self) -> &'static str {
+        match self.dev_id() {
+            0x410 => "STM32F10x medium-density",
+            0x412 => "STM32F10x low-density",
+            0x414 => "STM32F10x high-density",
+            0x430 => "STM32F10x XL-density",
+            0x418 => "STM32F10x connectivity line",
+            0x495 => "STM32WB5x",
+            0x496 => "STM32WB3x",
+            _ => "unknown device",
+        }
+    }
+
+    /// Lines shown in the log.
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("UID: {}", self.uid_hex()),
+            format!("Flash size: {} KiB", self.flash_size_kb),
+            format!(
+                "IDCODE: 0x{:08X} ({}, rev 0x{:04X})",
+                self.idcode,
+                self.device_name(),
+                self.rev_id()
+            ),
+        ];
+        if let Some(dp_idcode) = self.dp_idcode {
+            lines.push(format!("DP IDCODE: 0x{dp_idcode:08X}"));
+        }
+        lines
+    }
+}
+
+/// STM32F103 and the pin-compatible clones found on boards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum F1Variant {
+    /// Also what profiles saved with the former `Stm32F103xB` family load as.
+    #[default]
+    #[serde(alias = "Stm32F103xB")]
+    Stm32,
+    Cks32,
+    Apm32,
+    Gd32,
+    /// Answers like an F103 but matches nothing we know.
+    Unknown,
+}
+
+impl F1Variant {
+    /// Known clones are told apart by their SW-DP IDCODE, then by the
+    /// revision in DBGMCU_IDCODE when the DP is the same as ST's.
+    pub fn detect(id: &DeviceId) -> Self {
+        match (id.dp_idcode, id.rev_id()) {
+            (Some(0x2BA0_1477), _) => F1Variant::Cks32,
+            (Some(0x4BA0_0477), _) => F1Variant::Apm32,
+            (Some(0x1BA0_1477), 0x1303) => F1Variant::Gd32,
+            (Some(0x1BA0_1477), _) | (None, _) => F1Variant::Stm32,
+            _ => F1Variant::Unknown,
+        }
+    }
+
+    /// SW-DP IDCODE the variant is known to answer with.
+    fn known_dp_idcode(&self) -> u32 {
+        match self {
+            F1Variant::Cks32 => 0x2BA0_1477,
+            F1Variant::Apm32 => 0x4BA0_0477,
+            _ => STM32F1_CPUTAPID,
+        }
+    }
+
+    /// OpenOCD settings that let `target/stm32f1x.cfg` work with the chip,
+    /// from its device ID when it could be read.
+    pub fn settings(&self, id: Option<&DeviceId>) -> TargetSettings {
+        match self {
+            F1Variant::Stm32 => TargetSettings::default(),
+            // The clones are accepted with their own DP IDCODE. The flash
+            // bank size is still probed by the flash driver.
+            _ => TargetSettings {
+                cputapid: id
+                    .and_then(|id| id.dp_idcode)
+                    .unwrap_or(self.known_dp_idcode()),
+                ..TargetSettings::default()
+            },
+        }
+    }
+}
+
+/// Host chip families a board profile can expect.
+pub const ALL_F1_VARIANTS: [F1Variant; 4] = [
+    F1Variant::Stm32,
+    F1Variant::Cks32,
+    F1Variant::Apm32,
+    F1Variant::Gd32,
+];
+
+impl std::fmt::Display for F1Variant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            F1Variant::Stm32 => "STM32F103",
+            F1Variant::Cks32 => "CKS32F103",
+            F1Variant::Apm32 => "APM32F103",
+            F1Variant::Gd32 => "GD32F103",
+            F1Variant::Unknown => "unknown STM32F103 compatible",
+        })
+    }
+}
+
+/// SW-DP IDCODE expected by `target/stm32f1x.cfg`.
+pub const STM32F1_CPUTAPID: u32 = 0x1BA0_1477;
+
+/// Probe and variables set before the target configuration is sourced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TargetSettings {
+    pub probe: KnownProbe,
+    pub cputapid: u32,
+}
+
+impl Default for TargetSettings {
+    fn default() -> Self {
+        Self {
+            probe: KnownProbe::default(),
+            cputapid: STM32F1_CPUTAPID,
+        }
+    }
+}
+
+impl TargetSettings {
+    pub fn openocd_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = self
+            .probe
+            .openocd_args()
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        args.push("-c".into());
+        args.push(format!("set CPUTAPID 0x{:08x}", self.cputapid));
+        args
+    }
+}
+
+/// IDCODE column of the first TAP in a `scan_chain` table:
+/// ` 0 stm32f1x.cpu  Y  0x1ba01477 0x00000000  4 0x01  0x0f`.
+pub fn parse_tap_idcode(lines: &[&str]) -> Option<u32> {
+    lines.iter().find_map(|line| {
+        let mut columns = line.split_whitespace();
+        columns.next()?.parse::<u32>().ok()?;
+        if !columns.next()?.ends_with(".cpu") {
+            return None;
+        }
+        columns
+            .find_map(|c| c.strip_prefix("0x"))
+            .and_then(|c| u32::from_str_radix(c, 16).ok())
+    })
+}
+
+/// Address -> values of every `0xADDRESS: value value...` line.
+pub fn parse_memory_dumps<'a>(lines: impl IntoIterator<Item = &'a str>) -> HashMap<u32, Vec<u32>> {
+    let mut dumps = HashMap::new();
+
+    for line in lines {
+        let (address, values) = match line.trim().split_once(':') {
+            Some(parts) => parts,
+            None => continue,
+        };
+        let address = match address
+            .strip_prefix("0x")
+            .and_then(|a| u32::from_str_radix(a, 16).ok())
+        {
+            Some(address) => address,
+            None => continue,
+        };
+        let values: Result<Vec<u32>, _> = values
+            .split_whitespace()
+            .map(|v| u32::from_str_radix(v, 16))
+            .collect();
+
+        if let Ok(values) = values {
+            if !values.is_empty() {
+                dumps.insert(address, values);
+            }
+        }
+    }
+
+    dumps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_openocd_dumps() {
+        let output = [
+            "Info : STLINK V2J37S7 (API v2) VID:PID 0483:3748",
+            " 0 stm32f1x.cpu          Y     0x2ba01477 0x00000000     4 0x01  0x0f",
+            "0x1ffff7e8: 066dff49 48527751 87223342 ",
+            "0x1ffff7e0: 0080 ",
+            "0xe0042000: 20036410 ",
+        ];
+
+        let id = DeviceId::parse(SwdTarget::Stm32F1, output).unwrap();
+        assert_eq!(id.uid_hex(), "8722334248527751066DFF49");
+        assert_eq!(id.flash_size_kb, 128);
+        assert_eq!(id.dev_id(), 0x410);
+        assert_eq!(id.rev_id(), 0x2003);
+        assert_eq!(id.dp_idcode, Some(0x2BA0_1477));
+        assert_eq!(F1Variant::detect(&id), F1Variant::Cks32);
+        assert_eq!(
+            F1Variant::detect(&id).settings(Some(&id)).openocd_args(),
+            [
+                "-f",
+                "interface/stlink.cfg",
+                "-c",
+                "transport select hla_swd",
+                "-c",
+                "set CPUTAPID 0x2ba01477"
+            ]
+        );
+    }
+
+    #[test]
+    fn genuine_stm32_uses_stock_settings() {
+        let output = [
+            " 0 stm32f1x.cpu          Y     0x1ba01477 0x00000000     4 0x01  0x0f",
+            "0x1ffff7e8: 066dff49 48527751 87223342 ",
+            "0x1ffff7e0: 0040 ",
+            "0xe0042000: 20036410 ",
+        ];
+
+        let id = DeviceId::parse(SwdTarget::Stm32F1, output).unwrap();
+        assert_eq!(F1Variant::detect(&id), F1Variant::Stm32);
+        assert_eq!(
+            F1Variant::detect(&id).settings(Some(&id)).openocd_args(),
+            [
+                "-f",
+                "interface/stlink.cfg",
+                "-c",
+                "transport select hla_swd",
+                "-c",
+                "set CPUTAPID 0x1ba01477"
+            ]
+        );
+    }
+
+    #[test]
+    fn profile_settings_without_device_id() {
+        let settings = TargetSettings {
+            probe: KnownProbe::CmsisDap,
+            ..F1Variant::Apm32.settings(None)
+        };
+        assert_eq!(
+            settings.openocd_args(),
+            [
+                "-f",
+                "interface/cmsis-dap.cfg",
+                "-c",
+                "transport select swd",
+                "-c",
+                "set CPUTAPID 0x4ba00477"
+            ]
+        );
+    }
+}
@@ -87,3 +87,20 @@ pub fn get_settings_dir() -> Result<PathBuf, String> {
 
     Ok(tmp)
 }
+
+pub fn get_library_dir() -> Result<PathBuf, String> {
+    let base = get_base_dir()?;
+
+    let library = base.join("library");
+
+    if !library.exists() {
+        match fs::create_dir_all(&library) {
+            Ok(_) => (),
+            Err(e) => {
+                return Err(format!("Failed to create library directory {e}"));
+            }
+        }
+    }
+
+    Ok(library)
+}
@@ -1,36 +1,271 @@
 use std::{
     fs,
-    path::PathBuf,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
     thread,
-    time::{Duration, SystemTime},
+    time::{Duration, Instant},
 };
 
+use async_io::Timer;
+use iced::futures::{channel::mpsc::Sender, SinkExt};
+use serde::{Deserialize, Serialize};
 use sysinfo::Disks;
 
-#[derive(Debug, Clone)]
+use crate::{
+    firmware_image::{self, FirmwareImage, ImageFormat},
+    log_entries::LogType,
+    ui::messages::{WithLogMessage, WithProgressMessage},
+    usb_ids::DAPLINK_VID,
+    utils,
+};
+
+const POLL_PERIOD: Duration = Duration::from_millis(250);
+/// Drives are listed at least this often even without a mount table change:
+/// the udev label links may show up after the mount itself.
+const FORCED_REFRESH_PERIOD: Duration = Duration::from_secs(2);
+const COPY_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct DiskResult {
+    /// Filesystem label when known, mount point name otherwise.
     pub name: String,
     pub path: PathBuf,
+    pub is_removable: bool,
+    /// VID/PID of the USB device backing the volume, if any.
+    pub usb_id: Option<(u16, u16)>,
+    /// Block device of the volume (Linux only), used to unmount it.
+    pub device: Option<PathBuf>,
+}
+
+impl DiskResult {
+    /// True if the volume is the drive called `name`. The label is compared
+    /// first; the mount point name is also accepted with the numeric suffix
+    /// udisks adds when the name is already taken (`STEAMI1`).
+    pub fn matches(&self, name: &str) -> bool {
+        if self.name == name {
+            return true;
+        }
+
+        match self.path.file_name().and_then(|f| f.to_str()) {
+            Some(mount_name) => match mount_name.strip_prefix(name) {
+                Some(suffix) => suffix.chars().all(|c| c.is_ascii_digit()),
+                None => false,
+            },
+            None => false,
+        }
+    }
+
+    pub fn is_daplink(&self) -> bool {
+        matches!(self.usb_id, Some((DAPLINK_VID, _)))
+    }
+}
+
+pub const DETAILS_FILENAME: &str = "DETAILS.TXT";
+/// Written by DAPLink on its drive after a rejected image.
+pub const FAIL_FILENAME: &str = "FAIL.TXT";
+
+/// Content of the `DETAILS.TXT` file DAPLink exposes on its drive, in both
+/// bootloader (MAINTENANCE) and interface mode.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DaplinkDetails {
+    fields: Vec<(String, String)>,
+}
+
+impl DaplinkDetails {
+    /// Parses the `Key: value` lines, comments (`#`) and other lines are
+    /// ignored.
+    pub fn parse(content: &str) -> Self {
+        let fields = content
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .filter_map(|line| line.split_once(':'))
+            .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+            .filter(|(key, _)| !key.is_empty())
+            .collect();
+
+        Self { fields }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn unique_id(&self) -> Option<&str> {
+        self.get("Unique ID")
+    }
+
+    /// The board ID is the first 4 characters of the unique ID.
+    pub fn board_id(&self) -> Option<&str> {
+        self.get("Board ID")
+            .or_else(|| self.unique_id().and_then(|id| id.get(..4)))
+    }
+
+    pub fn family_id(&self) -> Option<&str> {
+        self.get("Family ID")
+    }
+
+    pub fn hic_id(&self) -> Option<&str> {
+        self.get("HIC ID")
+    }
+
+    pub fn bootloader_version(&self) -> Option<&str> {
+        self.get("Bootloader Version")
+    }
+
+    pub fn interface_version(&self) -> Option<&str> {
+        self.get("Interface Version")
+    }
+
+    pub fn git_sha(&self) -> Option<&str> {
+        self.get("Git SHA")
+    }
+
+    /// "Bootloader" or "Interface".
+    pub fn mode(&self) -> Option<&str> {
+        self.get("Daplink Mode")
+    }
+
+    /// Lines shown in the log for the fields we care about.
+    pub fn summary(&self) -> Vec<String> {
+        [
+            ("Unique ID", self.unique_id()),
+            ("Board ID", self.board_id()),
+            ("Family ID", self.family_id()),
+            ("HIC ID", self.hic_id()),
+            ("Mode", self.mode()),
+            ("Bootloader version", self.bootloader_version()),
+            ("Interface version", self.interface_version()),
+            ("Git SHA", self.git_sha()),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.map(|v| format!("{key}: {v}")))
+        .collect()
+    }
+
+    pub fn verify_mode(&self, expected_mode: &str) -> Result<(), String> {
+        match self.mode() {
+            Some(mode) if mode.eq_ignore_ascii_case(expected_mode) => Ok(()),
+            Some(mode) => Err(format!(
+                "DAPLink is in '{mode}' mode, '{expected_mode}' was expected"
+            )),
+            None => Err(format!("No DAPLink mode in {DETAILS_FILENAME}")),
+        }
+    }
+
+    /// Checks that the running DAPLink comes from `image`: the mode must be
+    /// `expected_mode` and the Git SHA reported by DAPLink must be embedded
+    /// in the image (DAPLink builds store it as a plain string).
+    pub fn verify_image(&self, expected_mode: &str, image: &Path) -> Result<(), String> {
+        self.verify_mode(expected_mode)?;
+
+        let sha = match self.git_sha() {
+            Some(sha) if !sha.is_empty() => sha,
+            _ => return Err(format!("No Git SHA in {DETAILS_FILENAME}")),
+        };
+
+        // HEX, ELF and S-record files are flattened first, the SHA is only
+        // readable in the raw bytes.
+        let content = FirmwareImage::load(image)?.data;
+
+        if content
+            .windows(sha.len())
+            .any(|w| w.eq_ignore_ascii_case(sha.as_bytes()))
+        {
+            Ok(())
+        } else {
+            Err(format!(
+                "Git SHA {sha} was not found in '{}', the installed firmware is not the selected one",
+                image.file_name().and_then(|f| f.to_str()).unwrap_or("undefined")
+            ))
+        }
+    }
+}
+
+/// Finds the drive called `name`, preferring volumes backed by a DAPLink
+/// USB device when several match.
+pub async fn find_disk(name: &str) -> Option<DiskResult> {
+    let disks = list_disks().await;
+    let mut matching = disks.iter().filter(|d| d.matches(name));
+
+    matching
+        .clone()
+        .find(|d| d.is_daplink())
+        .or_else(|| matching.next())
+        .cloned()
+}
+
+/// Mount names DAPLink is known to use on our boards.
+pub const KNOWN_DAPLINK_NAMES: [&str; 4] = ["STEAMI", "DIS_L4IOT", "DAPLINK", "MAINTENANCE"];
+
+/// Names (lowercase) of mount points that belong to the system, never a
+/// DAPLink drive.
+const SYSTEM_DISK_NAMES: [&str; 12] = [
+    "", "/", "c:", "c:\\", "boot", "efi", "home", "root", "system", "windows", "recovery", "var",
+];
+
+/// True if the name looks like a system volume rather than a DAPLink drive.
+pub fn is_system_disk_name(name: &str) -> bool {
+    SYSTEM_DISK_NAMES.contains(&name.trim().to_lowercase().as_str())
+}
+
+/// `get_list_disks` on its own thread: refreshing the disk list can take a
+/// while, the async waits must not stall the UI.
+pub async fn list_disks() -> Vec<DiskResult> {
+    utils::unblock(get_list_disks).await
 }
 
 pub fn get_list_disks() -> Vec<DiskResult> {
     let mut result = Vec::new();
 
+    #[cfg(target_os = "linux")]
+    let labels = linux::read_labels();
+
     for disk in Disks::new_with_refreshed_list().list() {
         #[cfg(target_os = "windows")]
         match disk.name().to_str() {
             Some(s) => result.push(DiskResult {
                 name: s.into(),
                 path: disk.mount_point().into(),
+                is_removable: disk.is_removable(),
+                usb_id: None,
+                device: None,
             }),
             None => (),
         };
 
-        #[cfg(not(target_os = "windows"))]
+        #[cfg(target_os = "linux")]
+        {
+            let device = PathBuf::from(disk.name());
+            let label = linux::label_of(&labels, &device);
+            let mount_name = disk.mount_point().file_name().and_then(|f| f.to_str());
+
+            if let Some(s) = label.as_deref().or(mount_name) {
+                result.push(DiskResult {
+                    name: s.into(),
+                    path: disk.mount_point().into(),
+                    is_removable: disk.is_removable(),
+                    usb_id: linux::usb_id_of(&device),
+                    device: Some(device.clone()),
+                });
+            }
+        }
+
+        #[cfg(not(any(target_os = "windows", target_os = "linux")))]
         match disk.mount_point().file_name().and_then(|f| f.to_str()) {
             Some(s) => result.push(DiskResult {
                 name: s.into(),
                 path: disk.mount_point().into(),
+                is_removable: disk.is_removable(),
+                usb_id: None,
+                device: None,
             }),
             None => (),
         };
@@ -39,40 +274,710 @@ pub fn get_list_disks() -> Vec<DiskResult> {
     result
 }
 
-pub async fn copy_file_to_disk(disk_name: String, file: PathBuf) -> Result<(), String> {
-    let disk = match get_list_disks().iter().find(|x| x.name == *disk_name) {
-        Some(d) => d.clone(),
+/// Label and USB identity lookups through udev's `/dev/disk/by-label` links
+/// and sysfs, so the drive name does not depend on the automounter.
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use crate::usb_ids;
+
+    const BY_LABEL_DIR: &str = "/dev/disk/by-label";
+
+    /// (canonical device path, label) for every labelled block device.
+    pub fn read_labels() -> Vec<(PathBuf, String)> {
+        let entries = match fs::read_dir(BY_LABEL_DIR) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+
+        entries
+            .flatten()
+            .filter_map(|entry| {
+                let device = fs::canonicalize(entry.path()).ok()?;
+                let label = unescape_label(entry.file_name().to_str()?);
+                Some((device, label))
+            })
+            .collect()
+    }
+
+    pub fn label_of(labels: &[(PathBuf, String)], device: &Path) -> Option<String> {
+        let device = fs::canonicalize(device).ok()?;
+        labels
+            .iter()
+            .find(|(d, _)| *d == device)
+            .map(|(_, label)| label.clone())
+    }
+
+    /// Walks up the sysfs device tree of the block device until the USB
+    /// device node carrying `idVendor`/`idProduct`.
+    pub fn usb_id_of(device: &Path) -> Option<(u16, u16)> {
+        let block_name = fs::canonicalize(device).ok()?.file_name()?.to_owned();
+        let mut sys_path = fs::canonicalize(Path::new("/sys/class/block").join(block_name)).ok()?;
+
+        loop {
+            if let (Some(vid), Some(pid)) = (
+                usb_ids::read_sysfs_hex(&sys_path.join("idVendor")),
+                usb_ids::read_sysfs_hex(&sys_path.join("idProduct")),
+            ) {
+                return Some((vid, pid));
+            }
+
+            if !sys_path.pop() || sys_path == Path::new("/sys") {
+                return None;
+            }
+        }
+    }
+
+    /// udev escapes unsafe characters in link names as `\xNN`.
+    pub fn unescape_label(raw: &str) -> String {
+        let mut result = String::new();
+        let mut rest = raw;
+
+        while let Some(idx) = rest.find("\\x") {
+            result.push_str(&rest[..idx]);
+            let code = rest
+                .get(idx + 2..idx + 4)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match code {
+                Some(code) => {
+                    result.push(code as char);
+                    rest = &rest[idx + 4..];
+                }
+                None => {
+                    result.push_str("\\x");
+                    rest = &rest[idx + 2..];
+                }
+            }
+        }
+
+        result.push_str(rest);
+        result
+    }
+}
+
+/// How hard `copy_file_to_disk` makes sure the file really reached the
+/// drive before DAPLink starts programming it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CopyOptions {
+    /// `fsync` the file and its directory once written.
+    pub sync: bool,
+    /// Unmount the volume once written, forcing the OS to flush everything.
+    pub unmount: bool,
+}
+
+impl Default for CopyOptions {
+    fn default() -> Self {
+        Self {
+            sync: true,
+            unmount: false,
+        }
+    }
+}
+
+/// Copies `file` on the drive then waits for DAPLink to process it: the drive
+/// is unmounted and, when the image is rejected, remounted with a
+/// `FAIL.TXT` whose reason is returned as the error. A drive that is not
+/// unmounted means DAPLink ignored the file. When we unmounted it ourselves,
+/// the block device going away (USB re-enumeration) is waited for instead.
+///
+/// `next_drive` is the drive DAPLink comes back as when it is not
+/// `disk_name` (bootloader switching to interface mode): it ends the wait as
+/// soon as it shows up.
+///
+/// The file is not read back: a DAPLink drive is emulated and does not keep
+/// what was written, so reading it would only return the OS cache. The
+/// result of the drive (`FAIL.TXT` or not) is the check.
+///
+/// The copy runs on its own thread; the progress and timings are sent to
+/// `sender`.
+pub async fn copy_file_to_disk<MSG>(
+    disk_name: String,
+    file: PathBuf,
+    next_drive: Option<String>,
+    timeout: Duration,
+    options: CopyOptions,
+    mut sender: Option<&mut Sender<MSG>>,
+) -> Result<(), String>
+where
+    MSG: WithProgressMessage + WithLogMessage,
+{
+    let disk = match find_disk(&disk_name).await {
+        Some(d) => d,
         None => return Err(format!("Unable to find '{disk_name}' disk.")),
     };
 
-    match thread::spawn(move || {
-        fs::copy(
-            &file,
-            disk.path.join(file.file_name().unwrap().to_str().unwrap()),
-        )
-    })
-    .join()
-    {
-        Ok(r) => match r {
-            Ok(_) => Ok(()),
-            Err(e) => Err(format!("Copy error : {e}")),
+    // DAPLink only understands binary and Intel HEX files.
+    let (file, format) = firmware_image::prepare_for_drive(&file)?;
+    if matches!(format, ImageFormat::Elf | ImageFormat::Srec) {
+        if let Some(ref mut s) = sender {
+            let _ = s
+                .send(MSG::log(LogType::Info(format!(
+                    "    {format} image converted to Intel HEX"
+                ))))
+                .await;
+        }
+    }
+
+    let file_name = file
+        .file_name()
+        .ok_or_else(|| "The file path has no file name.".to_string())?;
+    let destination = disk.path.join(file_name);
+    let total = fs::metadata(&file)
+        .map_err(|e| format!("Copy error : {e}"))?
+        .len();
+
+    let device = disk.device.clone();
+    let copied = Arc::new(AtomicU64::new(0));
+    let thread_copied = copied.clone();
+    let copy_thread =
+        thread::spawn(move || durable_copy(&file, &destination, &disk, options, &thread_copied));
+
+    let mut last_sent = None;
+    loop {
+        let done = copy_thread.is_finished();
+        let current = copied.load(Ordering::Relaxed);
+
+        if last_sent != Some(current) {
+            if let Some(ref mut s) = sender {
+                let _ = s.send(MSG::progress(current, total)).await;
+            }
+            last_sent = Some(current);
+        }
+
+        if done {
+            break;
+        }
+
+        Timer::after(Duration::from_millis(100)).await;
+    }
+
+    let report = match copy_thread.join() {
+        Ok(r) => r?,
+        Err(e) => return Err(format!("Copy thread error: {:#?}", e)),
+    };
+
+    if let Some(ref mut s) = sender {
+        for line in report.lines(total) {
+            let _ = s.send(MSG::log(LogType::Info(line))).await;
+        }
+        let _ = s
+            .send(MSG::log(LogType::Info(
+                "    No read-back: DAPLink drives do not keep the copied file".into(),
+            )))
+            .await;
+    }
+
+    // Our own unmount already removed the drive, only the block device
+    // disappearing shows that DAPLink reset. Without one, there is nothing
+    // left to tell them apart and the remount below is the only check.
+    let processed = match (report.unmount, device) {
+        (Some(_), Some(device)) => wait_for_device_removal(&device, timeout).await,
+        (Some(_), None) => true,
+        (None, _) => wait_for_drive_presence(&disk_name, false, timeout).await,
+    };
+    if !processed {
+        return Err(format!(
+            "'{disk_name}' was not unmounted within {} s, DAPLink did not process the file.",
+            timeout.as_secs()
+        ));
+    }
+
+    let mut names = vec![disk_name.as_str()];
+    names.extend(next_drive.as_deref());
+    match wait_for_any_drive(&names, timeout).await {
+        Some(0) => match find_disk(&disk_name).await {
+            Some(disk) => check_fail_file(&disk.path),
+            None => Ok(()),
         },
-        Err(e) => Err(format!("Copy thread error: {:#?}", e)),
+        // Either the next drive is already there, or nothing came back and
+        // the caller's own wait reports it.
+        _ => Ok(()),
     }
 }
 
+/// Timings and checks of one `durable_copy`.
+#[derive(Debug, Default)]
+struct CopyReport {
+    write: Duration,
+    sync: Option<Duration>,
+    unmount: Option<Duration>,
+    warnings: Vec<String>,
+}
+
+impl CopyReport {
+    fn lines(&self, size: u64) -> Vec<String> {
+        let mut lines = vec![format!(
+            "    Copied {size} bytes in {:.2} s",
+            self.write.as_secs_f32()
+        )];
+
+        if let Some(sync) = self.sync {
+            lines.push(format!("    Synced in {:.2} s", sync.as_secs_f32()));
+        }
+        if let Some(unmount) = self.unmount {
+            lines.push(format!("    Unmounted in {:.2} s", unmount.as_secs_f32()));
+        }
+        lines.extend(self.warnings.iter().map(|w| format!("    {w}")));
+
+        lines
+    }
+}
+
+fn durable_copy(
+    source: &Path,
+    destination: &Path,
+    disk: &DiskResult,
+    options: CopyOptions,
+    copied: &AtomicU64,
+) -> Result<CopyReport, String> {
+    let mut report = CopyReport::default();
+
+    let start = Instant::now();
+    let output =
+        copy_with_progress(source, destination, copied).map_err(|e| format!("Copy error : {e}"))?;
+    report.write = start.elapsed();
+
+    if options.sync {
+        let start = Instant::now();
+        output
+            .sync_all()
+            .map_err(|e| format!("Failed to sync the file ({e})"))?;
+        // Directory entries are only durable once the directory itself is
+        // synced. Windows cannot open a directory as a file, nothing to do.
+        if let Ok(dir) = fs::File::open(&disk.path) {
+            let _ = dir.sync_all();
+        }
+        report.sync = Some(start.elapsed());
+    }
+    drop(output);
+
+    if options.unmount {
+        let start = Instant::now();
+        match unmount(disk) {
+            Ok(_) => report.unmount = Some(start.elapsed()),
+            Err(e) => report.warnings.push(format!("Unmount failed: {e}")),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Asks the OS to unmount the volume, as a regular user.
+fn unmount(disk: &DiskResult) -> Result<(), String> {
+    #[cfg(target_os = "linux")]
+    let output = match disk.device.as_ref() {
+        Some(device) => Command::new("udisksctl")
+            .arg("unmount")
+            .arg("--block-device")
+            .arg(device)
+            .output(),
+        None => Command::new("umount").arg(&disk.path).output(),
+    };
+
+    #[cfg(target_os = "macos")]
+    let output = Command::new("diskutil")
+        .arg("unmount")
+        .arg(&disk.path)
+        .output();
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    let output: io::Result<std::process::Output> = {
+        let _ = disk;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "not supported on this system",
+        ))
+    };
+
+    match output {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => Err(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// CRC-32 (IEEE 802.3), the one used by zip and by DAPLink.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+fn copy_with_progress(
+    source: &Path,
+    destination: &Path,
+    copied: &AtomicU64,
+) -> io::Result<fs::File> {
+    let mut input = fs::File::open(source)?;
+    let mut output = fs::File::create(destination)?;
+    let mut buffer = vec![0; COPY_CHUNK_SIZE];
+
+    loop {
+        let read = input.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        output.write_all(&buffer[..read])?;
+        copied.fetch_add(read as u64, Ordering::Relaxed);
+    }
+
+    output.flush()?;
+    Ok(output)
+}
+
+/// Returns the reason found in `FAIL.TXT` if the file exists on the drive.
+pub fn check_fail_file(disk_path: &Path) -> Result<(), String> {
+    match fs::read_to_string(disk_path.join(FAIL_FILENAME)) {
+        Ok(content) => Err(parse_fail_reason(&content)),
+        Err(_) => Ok(()),
+    }
+}
+
+/// `FAIL.TXT` holds `error: <reason>` and `type: <type>` lines.
+fn parse_fail_reason(content: &str) -> String {
+    let reason = content
+        .lines()
+        .find_map(|line| line.strip_prefix("error:"))
+        .map(|reason| reason.trim())
+        .unwrap_or_else(|| content.trim());
+
+    format!("DAPLink rejected the file: {reason}")
+}
+
+pub async fn read_details(disk_name: String) -> Result<DaplinkDetails, String> {
+    let disk = match find_disk(&disk_name).await {
+        Some(d) => d,
+        None => return Err(format!("Unable to find '{disk_name}' disk.")),
+    };
+
+    match fs::read_to_string(disk.path.join(DETAILS_FILENAME)) {
+        Ok(content) => Ok(DaplinkDetails::parse(&content)),
+        Err(e) => Err(format!(
+            "Unable to read {DETAILS_FILENAME} on '{disk_name}' ({e})"
+        )),
+    }
+}
+
+/// DAPLink "magic file" commands: an empty file with this name copied at the
+/// root of the drive triggers an action (`.ACT`) or changes a persistent
+/// setting (`.CFG`). Mode switching is only accepted when automation is
+/// allowed, and automation itself is only changed while the reset button is
+/// held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DaplinkCommand {
+    #[default]
+    StartBootloader,
+    StartInterface,
+    AutoResetOn,
+    AutoResetOff,
+    AutomationOn,
+    AutomationOff,
+    OverflowOn,
+    OverflowOff,
+    Assert,
+}
+
+pub const ALL_DAPLINK_COMMANDS: [DaplinkCommand; 9] = [
+    DaplinkCommand::StartBootloader,
+    DaplinkCommand::StartInterface,
+    DaplinkCommand::AutoResetOn,
+    DaplinkCommand::AutoResetOff,
+    DaplinkCommand::AutomationOn,
+    DaplinkCommand::AutomationOff,
+    DaplinkCommand::OverflowOn,
+    DaplinkCommand::OverflowOff,
+    DaplinkCommand::Assert,
+];
+
+impl DaplinkCommand {
+    pub fn filename(&self) -> &'static str {
+        match self {
+            DaplinkCommand::StartBootloader => "START_BL.ACT",
+            DaplinkCommand::StartInterface => "START_IF.ACT",
+            DaplinkCommand::AutoResetOn => "AUTO_RST.CFG",
+            DaplinkCommand::AutoResetOff => "HARD_RST.CFG",
+            DaplinkCommand::AutomationOn => "AUTO_ON.CFG",
+            DaplinkCommand::AutomationOff => "AUTO_OFF.CFG",
+            DaplinkCommand::OverflowOn => "OVFL_ON.CFG",
+            DaplinkCommand::OverflowOff => "OVFL_OFF.CFG",
+            DaplinkCommand::Assert => "ASSERT.ACT",
+        }
+    }
+
+    /// What DAPLink needs to accept the command, if anything.
+    fn requirement(&self) -> Option<&'static str> {
+        match self {
+            DaplinkCommand::StartBootloader | DaplinkCommand::StartInterface => {
+                Some("Allow automation first.")
+            }
+            DaplinkCommand::AutomationOn | DaplinkCommand::AutomationOff => {
+                Some("Hold the reset button while sending the command.")
+            }
+            _ => None,
+        }
+    }
+
+    /// `DETAILS.TXT` field and value expected once the command is applied.
+    fn expected_detail(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            DaplinkCommand::StartBootloader => Some(("Daplink Mode", "Bootloader")),
+            DaplinkCommand::StartInterface => Some(("Daplink Mode", "Interface")),
+            DaplinkCommand::AutoResetOn => Some(("Auto Reset", "1")),
+            DaplinkCommand::AutoResetOff => Some(("Auto Reset", "0")),
+            DaplinkCommand::AutomationOn => Some(("Automation allowed", "1")),
+            DaplinkCommand::AutomationOff => Some(("Automation allowed", "0")),
+            DaplinkCommand::OverflowOn => Some(("Overflow detection", "1")),
+            DaplinkCommand::OverflowOff => Some(("Overflow detection", "0")),
+            DaplinkCommand::Assert => None,
+        }
+    }
+}
+
+/// Sends `command` to the DAPLink mounted as `disk_name`, waits for the drive
+/// to come back as `remount_name` and checks the result in `DETAILS.TXT`.
+pub async fn send_daplink_command(
+    disk_name: String,
+    remount_name: String,
+    command: DaplinkCommand,
+    timeout: Duration,
+) -> Result<DaplinkDetails, String> {
+    let disk = match find_disk(&disk_name).await {
+        Some(d) => d,
+        None => return Err(format!("Unable to find '{disk_name}' disk.")),
+    };
+
+    fs::write(disk.path.join(command.filename()), b"")
+        .map_err(|e| format!("Failed to write {} ({e})", command.filename()))?;
+
+    if !wait_for_drive_presence(&disk_name, false, timeout).await {
+        return Err(format!(
+            "'{disk_name}' did not remount after {}. {}",
+            command.filename(),
+            command.requirement().unwrap_or_default()
+        ));
+    }
+
+    if !wait_for_drive_presence(&remount_name, true, timeout).await {
+        return Err(format!(
+            "TIMEOUT : The device '{remount_name}' was not found."
+        ));
+    }
+
+    let remounted = find_disk(&remount_name)
+        .await
+        .ok_or_else(|| format!("Unable to find '{remount_name}' disk."))?;
+    check_fail_file(&remounted.path)?;
+
+    let details = read_details(remount_name).await?;
+
+    if let Some((key, expected)) = command.expected_detail() {
+        match details.get(key) {
+            Some(value) if value.eq_ignore_ascii_case(expected) => (),
+            value => {
+                return Err(format!(
+                    "{} was not applied ({key}: {}, expected {expected})",
+                    command.filename(),
+                    value.unwrap_or("missing")
+                ))
+            }
+        }
+    }
+
+    Ok(details)
+}
+
 pub async fn wait_for_drive(disk_name: String, timeout: Duration) -> bool {
-    let start = SystemTime::now();
+    wait_for_drive_presence(&disk_name, true, timeout).await
+}
 
-    while start.elapsed().unwrap() <= timeout {
-        let disk_list = get_list_disks();
+/// Waits until the drive is mounted (`present`) or unmounted (`!present`).
+async fn wait_for_drive_presence(disk_name: &str, present: bool, timeout: Duration) -> bool {
+    wait_for_disks(timeout, |disks| {
+        (disks.iter().any(|x| x.matches(disk_name)) == present).then_some(())
+    })
+    .await
+    .is_some()
+}
 
-        if disk_list.iter().any(|x| x.name == *disk_name) {
-            return true;
+/// Waits until the block device of an unmounted drive goes away.
+async fn wait_for_device_removal(device: &Path, timeout: Duration) -> bool {
+    let start = Instant::now();
+    while device.exists() {
+        if start.elapsed() > timeout {
+            return false;
+        }
+        Timer::after(POLL_PERIOD).await;
+    }
+    true
+}
+
+/// Waits until one of `names` is mounted, returns its index.
+async fn wait_for_any_drive(names: &[&str], timeout: Duration) -> Option<usize> {
+    wait_for_disks(timeout, |disks| {
+        names
+            .iter()
+            .position(|name| disks.iter().any(|x| x.matches(name)))
+    })
+    .await
+}
+
+/// Lists the drives each time the mount table changes until `found` returns
+/// something, or `timeout` elapses.
+async fn wait_for_disks<T>(
+    timeout: Duration,
+    found: impl Fn(&[DiskResult]) -> Option<T>,
+) -> Option<T> {
+    let start = Instant::now();
+    let mut watcher = MountWatcher::default();
+
+    loop {
+        if watcher.changed() {
+            if let Some(result) = found(&list_disks().await) {
+                return Some(result);
+            }
+        }
+
+        if start.elapsed() > timeout {
+            return None;
         }
 
-        thread::sleep(Duration::from_millis(500));
+        Timer::after(POLL_PERIOD).await;
     }
+}
+
+/// Cheap "did something get mounted or unmounted" check, so the full drive
+/// list is only rebuilt when needed. On Linux it compares
+/// `/proc/self/mountinfo`; other systems always report a change.
+#[derive(Default)]
+struct MountWatcher {
+    #[cfg(target_os = "linux")]
+    mount_table: Option<String>,
+    last_refresh: Option<Instant>,
+}
+
+impl MountWatcher {
+    fn changed(&mut self) -> bool {
+        let forced = !matches!(self.last_refresh, Some(t) if t.elapsed() < FORCED_REFRESH_PERIOD);
+
+        #[cfg(target_os = "linux")]
+        let table_changed = {
+            let current = fs::read_to_string("/proc/self/mountinfo").ok();
+            let changed = current.is_none() || current != self.mount_table;
+            self.mount_table = current;
+            changed
+        };
+
+        #[cfg(not(target_os = "linux"))]
+        let table_changed = true;
+
+        if forced || table_changed {
+            self.last_refresh = Some(Instant::now());
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl std::fmt::Display for DaplinkCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DaplinkCommand::StartBootloader => "Switch to bootloader (MAINTENANCE)",
+            DaplinkCommand::StartInterface => "Switch to interface",
+            DaplinkCommand::AutoResetOn => "Auto reset on",
+            DaplinkCommand::AutoResetOff => "Auto reset off (hard reset)",
+            DaplinkCommand::AutomationOn => "Automation on",
+            DaplinkCommand::AutomationOff => "Automation off",
+            DaplinkCommand::OverflowOn => "Overflow detection on",
+            DaplinkCommand::OverflowOff => "Overflow detection off",
+            DaplinkCommand::Assert => "Assert (test ASSERT.TXT)",
+        })
+    }
+}
 
-    false
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn disk(name: &str, mount: &str) -> DiskResult {
+        DiskResult {
+            name: name.into(),
+            path: PathBuf::from(mount),
+            is_removable: true,
+            usb_id: None,
+            device: None,
+        }
+    }
+
+    #[test]
+    fn matches_label_and_suffixed_mount_point() {
+        assert!(disk("STEAMI", "/media/user/1234-ABCD").matches("STEAMI"));
+        assert!(disk("STEAMI1", "/media/user/STEAMI1").matches("STEAMI"));
+        assert!(!disk("STEAMIX", "/media/user/STEAMIX").matches("STEAMI"));
+        assert!(!disk("DAPLINK", "/media/user/DAPLINK").matches("STEAMI"));
+    }
+
+    #[test]
+    fn parse_details_file() {
+        let details = DaplinkDetails::parse(
+            "# DAPLink Firmware - see https://daplink.io\n\
+             Unique ID: 0240000032044e45\n\
+             HIC ID: 97969900\n\
+             Daplink Mode: Interface\n\
+             Interface Version: 0257\n\
+             Bootloader Version: 0257\n\
+             Git SHA: 2bb1fc3d2ef2c6c4b8f02ab0ee7e3c3b0a17d2e8\n\
+             URL: https://os.mbed.com/platforms/\n",
+        );
+
+        assert_eq!(details.unique_id(), Some("0240000032044e45"));
+        assert_eq!(details.board_id(), Some("0240"));
+        assert_eq!(details.hic_id(), Some("97969900"));
+        assert_eq!(details.mode(), Some("Interface"));
+        assert_eq!(details.interface_version(), Some("0257"));
+        assert_eq!(
+            details.git_sha(),
+            Some("2bb1fc3d2ef2c6c4b8f02ab0ee7e3c3b0a17d2e8")
+        );
+        assert_eq!(details.get("URL"), Some("https://os.mbed.com/platforms/"));
+        assert_eq!(details.family_id(), None);
+    }
+
+    #[test]
+    fn crc32_reference_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn parse_fail_file() {
+        assert_eq!(
+            parse_fail_reason("error: The hex file cannot be decoded.\ntype: user\n"),
+            "DAPLink rejected the file: The hex file cannot be decoded."
+        );
+        assert_eq!(
+            parse_fail_reason("Unknown failure\n"),
+            "DAPLink rejected the file: Unknown failure"
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn unescape_udev_labels() {
+        assert_eq!(linux::unescape_label("MY\\x20DRIVE"), "MY DRIVE");
+        assert_eq!(linux::unescape_label("STEAMI"), "STEAMI");
+        assert_eq!(linux::unescape_label("BAD\\xZZ"), "BAD\\xZZ");
+    }
 }
@@ -0,0 +1,660 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::dirs;
+
+/// Largest hole allowed between two segments once the image is flattened,
+/// beyond it the file probably mixes flash and RAM addresses.
+const MAX_GAP: u32 = 1024 * 1024;
+/// Value of erased flash, used to fill the holes between segments.
+pub const ERASED_BYTE: u8 = 0xFF;
+const HEX_RECORD_SIZE: usize = 16;
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const PT_LOAD: u32 = 1;
+
+/// DAPLink stores its build key, HIC ID and version in the reserved vector
+/// table entries 7 to 9.
+const DAPLINK_INFO_OFFSET: usize = 0x1C;
+const DAPLINK_BUILD_KEY_BL: u32 = 0x9B93_9D93;
+const DAPLINK_BUILD_KEY_IF: u32 = 0x9B93_9E8F;
+const GIT_SHA_LEN: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Binary,
+    IntelHex,
+    Elf,
+    Srec,
+}
+
+pub const ALL_IMAGE_FORMATS: [ImageFormat; 4] = [
+    ImageFormat::Binary,
+    ImageFormat::IntelHex,
+    ImageFormat::Elf,
+    ImageFormat::Srec,
+];
+
+impl ImageFormat {
+    /// Detects the format from the content, the extension is not trusted.
+    pub fn detect(content: &[u8]) -> ImageFormat {
+        if content.starts_with(ELF_MAGIC) {
+            return ImageFormat::Elf;
+        }
+
+        let first = content.iter().find(|b| !b.is_ascii_whitespace());
+        let second = first.and_then(|_| {
+            content
+                .iter()
+                .skip_while(|b| b.is_ascii_whitespace())
+                .nth(1)
+        });
+
+        match (first, second) {
+            (Some(b':'), Some(c)) if c.is_ascii_hexdigit() => ImageFormat::IntelHex,
+            (Some(b'S'), Some(c)) if c.is_ascii_digit() => ImageFormat::Srec,
+            _ => ImageFormat::Binary,
+        }
+    }
+
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            ImageFormat::Binary => &["bin", "BIN"],
+            ImageFormat::IntelHex => &["hex", "HEX", "ihex"],
+            ImageFormat::Elf => &["elf", "ELF", "axf", "out"],
+            ImageFormat::Srec => &["srec", "SREC", "s19", "s28", "s37", "mot"],
+        }
+    }
+}
+
+impl std::fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ImageFormat::Binary => "Binary",
+            ImageFormat::IntelHex => "Intel HEX",
+            ImageFormat::Elf => "ELF",
+            ImageFormat::Srec => "Motorola S-record",
+        })
+    }
+}
+
+/// Which DAPLink program an image is, from its build key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DaplinkBuild {
+    Bootloader,
+    Interface,
+}
+
+impl std::fmt::Display for DaplinkBuild {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DaplinkBuild::Bootloader => "bootloader",
+            DaplinkBuild::Interface => "interface firmware",
+        })
+    }
+}
+
+/// A firmware file flattened to one contiguous block.
+#[derive(Debug, Clone)]
+pub struct FirmwareImage {
+    pub format: ImageFormat,
+    /// Load address of `data`, `None` for raw binaries which carry none.
+    pub base_address: Option<u32>,
+    pub data: Vec<u8>,
+    /// Address ranges (end excluded) actually present in the file, the holes
+    /// in `data` are not part of them.
+    pub ranges: Vec<(u32, u32)>,
+}
+
+impl FirmwareImage {
+    pub fn load(path: &Path) -> Result<FirmwareImage, String> {
+        let content = fs::read(path).map_err(|e| {
+            format!(
+                "Failed to read '{}'. Error: {e}",
+                path.to_str().unwrap_or("undefined")
+            )
+        })?;
+
+        Self::parse(&content).map_err(|e| {
+            format!(
+                "Invalid image '{}' ({e})",
+                path.file_name()
+                    .and_then(|f| f.to_str())
+                    .unwrap_or("undefined")
+            )
+        })
+    }
+
+    pub fn parse(content: &[u8]) -> Result<FirmwareImage, String> {
+        let format = ImageFormat::detect(content);
+
+        let segments = match format {
+            ImageFormat::Binary => {
+                return Ok(FirmwareImage {
+                    format,
+                    base_address: None,
+                    data: content.to_vec(),
+                    ranges: Vec::new(),
+                })
+            }
+            ImageFormat::IntelHex => parse_intel_hex(content)?,
+            ImageFormat::Elf => parse_elf(content)?,
+            ImageFormat::Srec => parse_srec(content)?,
+        };
+
+        let (base_address, data, ranges) = flatten(segments)?;
+
+        Ok(FirmwareImage {
+            format,
+            base_address: Some(base_address),
+            data,
+            ranges,
+        })
+    }
+
+    fn word_at(&self, offset: usize) -> Option<u32> {
+        self.data
+            .get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// First entry of the Cortex-M vector table.
+    pub fn initial_sp(&self) -> Option<u32> {
+        self.word_at(0)
+    }
+
+    pub fn reset_vector(&self) -> Option<u32> {
+        self.word_at(4)
+    }
+
+    /// True if the image starts with a plausible vector table: the stack
+    /// in SRAM and a Thumb reset handler.
+    pub fn has_vector_table(&self) -> bool {
+        match (self.initial_sp(), self.reset_vector()) {
+            (Some(sp), Some(reset)) => sp >> 28 == 0x2 && reset & 1 == 1,
+            _ => false,
+        }
+    }
+
+    pub fn daplink_build(&self) -> Option<DaplinkBuild> {
+        match self.word_at(DAPLINK_INFO_OFFSET)? {
+            DAPLINK_BUILD_KEY_BL => Some(DaplinkBuild::Bootloader),
+            DAPLINK_BUILD_KEY_IF => Some(DaplinkBuild::Interface),
+            _ => None,
+        }
+    }
+
+    pub fn daplink_hic_id(&self) -> Option<u32> {
+        self.daplink_build()?;
+        self.word_at(DAPLINK_INFO_OFFSET + 4)
+    }
+
+    /// Version as shown in `DETAILS.TXT` (`0257`).
+    pub fn daplink_version(&self) -> Option<String> {
+        self.daplink_build()?;
+        self.word_at(DAPLINK_INFO_OFFSET + 8)
+            .map(|v| format!("{v:04}"))
+    }
+
+    /// Board ID of an interface firmware, read from its word aligned
+    /// `board_info` structure: info version 1, family ID, then the 4 digits
+    /// ID.
+    pub fn daplink_board_id(&self) -> Option<String> {
+        if self.daplink_build()? != DaplinkBuild::Interface {
+            return None;
+        }
+
+        self.data
+            .windows(9)
+            .step_by(4)
+            .find(|w| {
+                w[..2] == [0x01, 0x00] && w[4..8].iter().all(u8::is_ascii_hexdigit) && w[8] == 0
+            })
+            .map(|w| String::from_utf8_lossy(&w[4..8]).into_owned())
+    }
+
+    /// Git SHA of the DAPLink build, stored as a NUL terminated string.
+    pub fn daplink_git_sha(&self) -> Option<String> {
+        self.daplink_build()?;
+
+        self.data
+            .windows(GIT_SHA_LEN + 2)
+            .find(|w| {
+                !w[0].is_ascii_hexdigit()
+                    && w[1..=GIT_SHA_LEN].iter().all(u8::is_ascii_hexdigit)
+                    && w[GIT_SHA_LEN + 1] == 0
+            })
+            .map(|w| String::from_utf8_lossy(&w[1..=GIT_SHA_LEN]).into_owned())
+    }
+
+    /// Lines shown in the log by the inspector.
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("Format: {}", self.format),
+            format!("Size: {} bytes", self.data.len()),
+        ];
+
+        if self.ranges.is_empty() {
+            lines.push("Address: none (raw binary)".into());
+        } else {
+            lines.push(format!("Ranges: {}", format_ranges(&self.ranges)));
+        }
+
+        if self.has_vector_table() {
+            lines.push(format!(
+                "Initial SP: 0x{:08X}, reset vector: 0x{:08X}",
+                self.initial_sp().unwrap_or_default(),
+                self.reset_vector().unwrap_or_default()
+            ));
+        } else {
+            lines.push("No Cortex-M vector table at the start of the image".into());
+        }
+
+        match self.daplink_build() {
+            Some(build) => {
+                lines.push(format!(
+                    "DAPLink {build}, version {}, HIC ID {:08X}",
+                    self.daplink_version().unwrap_or_default(),
+                    self.daplink_hic_id().unwrap_or_default()
+                ));
+                if let Some(board_id) = self.daplink_board_id() {
+                    lines.push(format!("Board ID: {board_id}"));
+                }
+                if let Some(sha) = self.daplink_git_sha() {
+                    lines.push(format!("Git SHA: {sha}"));
+                }
+            }
+            None => lines.push("No DAPLink build information".into()),
+        }
+
+        lines
+    }
+
+    pub fn to_intel_hex(&self) -> String {
+        let mut out = String::new();
+        let mut upper: Option<u16> = None;
+        let base = self.base_address.unwrap_or(0);
+
+        for (i, chunk) in self.data.chunks(HEX_RECORD_SIZE).enumerate() {
+            let address = base.wrapping_add((i * HEX_RECORD_SIZE) as u32);
+
+            // A record must not cross a 64 KiB boundary: with a 16 bytes
+            // record size it only happens when the base is unaligned.
+            let split = (0x1_0000 - (address & 0xFFFF)) as usize;
+            let (first, second) = chunk.split_at(split.min(chunk.len()));
+
+            for (address, chunk) in [
+                (address, first),
+                (address.wrapping_add(split as u32), second),
+            ] {
+                if chunk.is_empty() {
+                    continue;
+                }
+
+                let high = (address >> 16) as u16;
+                if upper != Some(high) {
+                    push_hex_record(&mut out, 0, 0x04, &high.to_be_bytes());
+                    upper = Some(high);
+                }
+                push_hex_record(&mut out, address as u16, 0x00, chunk);
+            }
+        }
+
+        push_hex_record(&mut out, 0, 0x01, &[]);
+        out
+    }
+}
+
+/// Returns a file DAPLink drives understand (binary or Intel HEX): the file
+/// itself when it already is, otherwise a HEX conversion in the tmp folder.
+pub fn prepare_for_drive(path: &Path) -> Result<(PathBuf, ImageFormat), String> {
+    let image = FirmwareImage::load(path)?;
+
+    match image.format {
+        ImageFormat::Binary | ImageFormat::IntelHex => Ok((path.to_path_buf(), image.format)),
+        ImageFormat::Elf | ImageFormat::Srec => {
+            let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("image");
+            let converted = dirs::get_tmp_dir()?.join(format!("{stem}.hex"));
+
+            fs::write(&converted, image.to_intel_hex())
+                .map_err(|e| format!("Failed to write the converted image ({e})"))?;
+
+            Ok((converted, image.format))
+        }
+    }
+}
+
+/// `0x08000000-0x08000FFF, ...` for ranges with an excluded end.
+pub fn format_ranges(ranges: &[(u32, u32)]) -> String {
+    let ranges: Vec<String> = ranges
+        .iter()
+        .map(|(start, end)| format!("0x{start:08X}-0x{:08X}", end - 1))
+        .collect();
+    ranges.join(", ")
+}
+
+/// Address ranges (end excluded) where `actual`, read at `base`, differs
+/// from `expected`. Missing bytes count as different.
+pub fn mismatch_ranges(expected: &[u8], actual: &[u8], base: u32) -> Vec<(u32, u32)> {
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+
+    for (offset, byte) in expected.iter().enumerate() {
+        if actual.get(offset) == Some(byte) {
+            continue;
+        }
+        let address = base + offset as u32;
+        match ranges.last_mut() {
+            Some(last) if last.1 == address => last.1 = address + 1,
+            _ => ranges.push((address, address + 1)),
+        }
+    }
+
+    ranges
+}
+
+fn push_hex_record(out: &mut String, address: u16, kind: u8, data: &[u8]) {
+    let mut bytes = vec![data.len() as u8];
+    bytes.extend_from_slice(&address.to_be_bytes());
+    bytes.push(kind);
+    bytes.extend_from_slice(data);
+
+    let sum = bytes.iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+    bytes.push(sum.wrapping_neg());
+
+    out.push(':');
+    for b in bytes {
+        out.push_str(&format!("{b:02X}"));
+    }
+    out.push('\n');
+}
+
+fn decode_hex_line(line: &str, number: usize) -> Result<Vec<u8>, String> {
+    if !line.len().is_multiple_of(2) || !line.is_ascii() {
+        return Err(format!("malformed record at line {number}"));
+    }
+
+    (0..line.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&line[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| format!("malformed record at line {number}"))
+}
+
+fn parse_intel_hex(content: &[u8]) -> Result<Vec<(u32, Vec<u8>)>, String> {
+    let text = std::str::from_utf8(content).map_err(|_| "not a text file".to_string())?;
+    let mut segments = Vec::new();
+    let mut offset = 0u32;
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let number = i + 1;
+        let bytes = match line.strip_prefix(':') {
+            Some(record) => decode_hex_line(record, number)?,
+            None => return Err(format!("missing ':' at line {number}")),
+        };
+
+        if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+            return Err(format!("bad record length at line {number}"));
+        }
+        if bytes.iter().fold(0u8, |acc, b| acc.wrapping_add(*b)) != 0 {
+            return Err(format!("bad checksum at line {number}"));
+        }
+
+        let address = u16::from_be_bytes([bytes[1], bytes[2]]) as u32;
+        let data = &bytes[4..bytes.len() - 1];
+
+        match bytes[3] {
+            0x00 => segments.push((offset.wrapping_add(address), data.to_vec())),
+            0x01 => return Ok(segments),
+            0x02 if data.len() == 2 => {
+                offset = (u16::from_be_bytes([data[0], data[1]]) as u32) << 4
+            }
+            0x04 if data.len() == 2 => {
+                offset = (u16::from_be_bytes([data[0], data[1]]) as u32) << 16
+            }
+            // Start addresses are not needed to program the flash.
+            0x03 | 0x05 => (),
+            kind => {
+                return Err(format!(
+                    "unsupported record type {kind:02X} at line {number}"
+                ))
+            }
+        }
+    }
+
+    Err("no end of file record".into())
+}
+
+fn parse_srec(content: &[u8]) -> Result<Vec<(u32, Vec<u8>)>, String> {
+    let text = std::str::from_utf8(content).map_err(|_| "not a text file".to_string())?;
+    let mut segments = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let number = i + 1;
+        let kind = match line.strip_prefix('S').and_then(|l| l.chars().next()) {
+            Some(kind) if line.is_ascii() => kind,
+            _ => return Err(format!("missing 'S' at line {number}")),
+        };
+        let bytes = decode_hex_line(&line[2..], number)?;
+
+        if bytes.is_empty() || bytes.len() != bytes[0] as usize + 1 {
+            return Err(format!("bad record length at line {number}"));
+        }
+        if bytes.iter().fold(0u8, |acc, b| acc.wrapping_add(*b)) != 0xFF {
+            return Err(format!("bad checksum at line {number}"));
+        }
+
+        let address_size = match kind {
+            '1' => 2,
+            '2' => 3,
+            '3' => 4,
+            // Header, record counts and start addresses.
+            '0' | '5' | '6' | '7' | '8' | '9' => continue,
+            _ => return Err(format!("unsupported record type S{kind} at line {number}")),
+        };
+
+        if bytes.len() < address_size + 2 {
+            return Err(format!("bad record length at line {number}"));
+        }
+
+        let address = bytes[1..=address_size]
+            .iter()
+            .fold(0u32, |acc, b| (acc << 8) | *b as u32);
+        let data = &bytes[address_size + 1..bytes.len() - 1];
+        segments.push((address, data.to_vec()));
+    }
+
+    Ok(segments)
+}
+
+/// Loadable segments of a 32 bits little endian ELF (what Cortex-M
+/// toolchains produce), at their physical (load) address.
+fn parse_elf(content: &[u8]) -> Result<Vec<(u32, Vec<u8>)>, String> {
+    let u16_at = |offset: usize| -> Result<u16, String> {
+        content
+            .get(offset..offset + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .ok_or_else(|| "truncated ELF file".to_string())
+    };
+    let u32_at = |offset: usize| -> Result<u32, String> {
+        content
+            .get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(|| "truncated ELF file".to_string())
+    };
+
+    if content.get(4) != Some(&1) || content.get(5) != Some(&1) {
+        return Err("only 32 bits little endian ELF files are supported".into());
+    }
+
+    let ph_offset = u32_at(0x1C)? as usize;
+    let ph_size = u16_at(0x2A)? as usize;
+    let ph_count = u16_at(0x2C)? as usize;
+    let mut segments = Vec::new();
+
+    for i in 0..ph_count {
+        let header = ph_offset + i * ph_size;
+        if u32_at(header)? != PT_LOAD {
+            continue;
+        }
+
+        let offset = u32_at(header + 4)? as usize;
+        let address = u32_at(header + 12)?;
+        let size = u32_at(header + 16)? as usize;
+        if size == 0 {
+            continue;
+        }
+
+        let data = content
+            .get(offset..offset + size)
+            .ok_or_else(|| "truncated ELF file".to_string())?;
+        segments.push((address, data.to_vec()));
+    }
+
+    Ok(segments)
+}
+
+type Flattened = (u32, Vec<u8>, Vec<(u32, u32)>);
+
+fn flatten(mut segments: Vec<(u32, Vec<u8>)>) -> Result<Flattened, String> {
+    segments.retain(|(_, data)| !data.is_empty());
+    segments.sort_by_key(|(address, _)| *address);
+
+    let base = match segments.first() {
+        Some((address, _)) => *address,
+        None => return Err("no data".into()),
+    };
+
+    let mut data: Vec<u8> = Vec::new();
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for (address, segment) in segments {
+        let offset = (address - base) as usize;
+
+        if offset < data.len() {
+            return Err(format!("overlapping data at 0x{address:08X}"));
+        }
+        if offset - data.len() > MAX_GAP as usize {
+            return Err(format!(
+                "hole of {} KiB before 0x{address:08X}, the file mixes distant memory regions",
+                (offset - data.len()) / 1024
+            ));
+        }
+
+        data.resize(offset, ERASED_BYTE);
+        data.extend_from_slice(&segment);
+
+        let end = address + segment.len() as u32;
+        match ranges.last_mut() {
+            Some(last) if last.1 == address => last.1 = end,
+            _ => ranges.push((address, end)),
+        }
+    }
+
+    Ok((base, data, ranges))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intel_hex_round_trip() {
+        let image = FirmwareImage {
+            format: ImageFormat::Binary,
+            base_address: Some(0x0800_FFF8),
+            data: (0..40).collect(),
+            ranges: Vec::new(),
+        };
+
+        let hex = image.to_intel_hex();
+        assert!(hex.starts_with(":020000040800F2\n"));
+        assert!(hex.ends_with(":00000001FF\n"));
+
+        let parsed = FirmwareImage::parse(hex.as_bytes()).unwrap();
+        assert_eq!(parsed.format, ImageFormat::IntelHex);
+        assert_eq!(parsed.base_address, Some(0x0800_FFF8));
+        assert_eq!(parsed.data, image.data);
+    }
+
+    #[test]
+    fn srec_with_gap() {
+        let srec = b"S00600004844521B\n\
+                     S3090800000001020304E4\n\
+                     S30908000008AABBCCDDD8\n\
+                     S70508000000F2\n";
+
+        let image = FirmwareImage::parse(srec).unwrap();
+        assert_eq!(image.format, ImageFormat::Srec);
+        assert_eq!(image.base_address, Some(0x0800_0000));
+        assert_eq!(
+            image.data,
+            [1, 2, 3, 4, 0xFF, 0xFF, 0xFF, 0xFF, 0xAA, 0xBB, 0xCC, 0xDD]
+        );
+        assert_eq!(
+            image.ranges,
+            [(0x0800_0000, 0x0800_0004), (0x0800_0008, 0x0800_000C)]
+        );
+    }
+
+    #[test]
+    fn daplink_interface_info() {
+        let mut data = vec![0u8; 0x100];
+        let words = [
+            (0x00, 0x2000_5000),
+            (0x04, 0x0800_C145),
+            (0x1C, DAPLINK_BUILD_KEY_IF),
+            (0x20, 0x9796_9908),
+            (0x24, 257),
+        ];
+        for (offset, word) in words {
+            data[offset..offset + 4].copy_from_slice(&u32::to_le_bytes(word));
+        }
+        data[0x40..0x49].copy_from_slice(b"\x01\x00\x05\x010778\0");
+        let sha = b"0123456789abcdef0123456789abcdef01234567\0";
+        data[0x80..0x80 + sha.len()].copy_from_slice(sha);
+
+        let image = FirmwareImage::parse(&data).unwrap();
+        assert!(image.has_vector_table());
+        assert_eq!(image.daplink_build(), Some(DaplinkBuild::Interface));
+        assert_eq!(image.daplink_version().as_deref(), Some("0257"));
+        assert_eq!(image.daplink_board_id().as_deref(), Some("0778"));
+        assert_eq!(
+            image.daplink_git_sha().as_deref(),
+            Some("0123456789abcdef0123456789abcdef01234567")
+        );
+    }
+
+    #[test]
+    fn mismatches_are_grouped_in_ranges() {
+        let expected = [0xFF; 8];
+        let actual = [0xFF, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0x12];
+
+        let ranges = mismatch_ranges(&expected, &actual, 0x0800_0000);
+        assert_eq!(
+            ranges,
+            [(0x0800_0001, 0x0800_0003), (0x0800_0006, 0x0800_0008)]
+        );
+        assert_eq!(
+            format_ranges(&ranges),
+            "0x08000001-0x08000002, 0x08000006-0x08000007"
+        );
+    }
+
+    #[test]
+    fn raw_binary_has_no_address() {
+        let image = FirmwareImage::parse(&[0x00, 0x50, 0x00, 0x20]).unwrap();
+        assert_eq!(image.format, ImageFormat::Binary);
+        assert_eq!(image.base_address, None);
+    }
+}
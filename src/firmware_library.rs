@@ -0,0 +1,192 @@
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::dirs;
+
+const INDEX_FILE: &str = "library.json";
+
+/// One imported image. The file is stored in the library folder, under
+/// its SHA-256, so that it can't change behind our back.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LibraryEntry {
+    pub sha256: String,
+    pub file_name: String,
+    pub version: String,
+    /// Where the file came from (usually a release URL).
+    pub source: String,
+    /// Board profile the image is meant for.
+    pub board: String,
+    /// Import date, seconds since the Unix epoch.
+    pub imported: u64,
+}
+
+impl LibraryEntry {
+    pub fn path(&self) -> Result<PathBuf, String> {
+        Ok(dirs::get_library_dir()?
+            .join(&self.sha256)
+            .join(&self.file_name))
+    }
+
+    /// `path`, once checked that the stored file still has the imported
+    /// content.
+    pub fn checked_path(&self) -> Result<PathBuf, String> {
+        let path = self.path()?;
+        let sha256 = sha256_file(&path)?;
+        if sha256 != self.sha256 {
+            return Err(format!(
+                "'{}' was modified in the library (SHA-256 {sha256}), import it again.",
+                self.file_name
+            ));
+        }
+
+        Ok(path)
+    }
+
+    pub fn short_sha(&self) -> &str {
+        self.sha256.get(..12).unwrap_or(&self.sha256)
+    }
+}
+
+impl std::fmt::Display for LibraryEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.file_name)?;
+        if !self.version.is_empty() {
+            write!(f, " {}", self.version)?;
+        }
+        if !self.board.is_empty() {
+            write!(f, " [{}]", self.board)?;
+        }
+        write!(f, " ({})", self.short_sha())
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct FirmwareLibrary {
+    entries: Vec<LibraryEntry>,
+}
+
+impl FirmwareLibrary {
+    pub fn load() -> Result<Self, String> {
+        let index = dirs::get_library_dir()?.join(INDEX_FILE);
+        if !index.exists() {
+            return Ok(Self::default());
+        }
+
+        let content =
+            fs::read(&index).map_err(|e| format!("Failed to read the library index ({e})"))?;
+        serde_json::from_slice(&content).map_err(|e| format!("Invalid library index ({e})"))
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let index = dirs::get_library_dir()?.join(INDEX_FILE);
+        let content = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+
+        fs::write(index, content).map_err(|e| format!("Failed to write the library index ({e})"))
+    }
+
+    pub fn entries(&self) -> &[LibraryEntry] {
+        &self.entries
+    }
+
+    /// Entry holding exactly the content of `sha256`, if imported.
+    pub fn find(&self, sha256: &str) -> Option<&LibraryEntry> {
+        self.entries.iter().find(|e| e.sha256 == sha256)
+    }
+
+    /// Copies `file` in the library. Importing a file already there only
+    /// updates its notes.
+    pub fn import(
+        &mut self,
+        file: &Path,
+        version: &str,
+        source: &str,
+        board: &str,
+    ) -> Result<LibraryEntry, String> {
+        let sha256 = sha256_file(file)?;
+        let file_name = file
+            .file_name()
+            .and_then(|f| f.to_str())
+            .ok_or_else(|| "The file path has no file name.".to_string())?
+            .to_string();
+        let imported = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        let entry = LibraryEntry {
+            sha256,
+            file_name,
+            version: version.trim().to_string(),
+            source: source.trim().to_string(),
+            board: board.trim().to_string(),
+            imported,
+        };
+
+        let destination = entry.path()?;
+        if let Some(folder) = destination.parent() {
+            fs::create_dir_all(folder)
+                .map_err(|e| format!("Failed to create the library folder ({e})"))?;
+        }
+        fs::copy(file, &destination).map_err(|e| format!("Failed to import the file ({e})"))?;
+
+        match self.entries.iter_mut().find(|e| e.sha256 == entry.sha256) {
+            Some(existing) => *existing = entry.clone(),
+            None => self.entries.push(entry.clone()),
+        }
+        self.save()?;
+
+        Ok(entry)
+    }
+}
+
+/// Lowercase hex SHA-256 of the file content.
+pub fn sha256_file(path: &Path) -> Result<String, String> {
+    let mut file = fs::File::open(path).map_err(|e| {
+        format!(
+            "Failed to read '{}'. Error: {e}",
+            path.to_str().unwrap_or("undefined")
+        )
+    })?;
+
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sha256_of_file() {
+        let path =
+            std::env::temp_dir().join(format!("easy_flash_sha256_test_{}.bin", std::process::id()));
+        fs::write(&path, b"abc").unwrap();
+
+        assert_eq!(
+            sha256_file(&path).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        let _ = fs::remove_file(path);
+    }
+}
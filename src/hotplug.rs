@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+use async_io::Timer;
+use iced::{
+    futures::{SinkExt, Stream},
+    stream::channel,
+    Subscription,
+};
+
+use crate::{
+    disk_tool::{self, DiskResult},
+    usb_ids::{self, UsbDevice},
+    utils,
+};
+
+const POLL_PERIOD: Duration = Duration::from_secs(1);
+
+/// What is plugged on the computer at a given time.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeviceSnapshot {
+    pub serial_ports: Vec<serialport::SerialPortInfo>,
+    pub disks: Vec<DiskResult>,
+    pub usb_devices: Vec<UsbDevice>,
+}
+
+impl DeviceSnapshot {
+    /// Refreshes the drive list and walks the serial ports and the USB bus:
+    /// slow, keep it off the async executor.
+    pub fn capture() -> Self {
+        Self {
+            serial_ports: serialport::available_ports().unwrap_or_default(),
+            disks: disk_tool::get_list_disks(),
+            usb_devices: usb_ids::list_usb_devices(),
+        }
+    }
+}
+
+/// Emits a new snapshot each time a serial port, a drive or a USB device
+/// appears or disappears. The first snapshot is sent right away.
+pub fn watch() -> Subscription<DeviceSnapshot> {
+    Subscription::run(poll_devices)
+}
+
+fn poll_devices() -> impl Stream<Item = DeviceSnapshot> {
+    channel(4, |mut output| async move {
+        let mut last: Option<DeviceSnapshot> = None;
+
+        loop {
+            let snapshot = utils::unblock(DeviceSnapshot::capture).await;
+
+            if last.as_ref() != Some(&snapshot) {
+                if output.send(snapshot.clone()).await.is_err() {
+                    break;
+                }
+                last = Some(snapshot);
+            }
+
+            Timer::after(POLL_PERIOD).await;
+        }
+    })
+}
@@ -5,12 +5,21 @@ use iced::{window, Settings, Size};
 use log_entries::LogEntries;
 use ui::main_window::MainWindow;
 
+mod board_profile;
+mod device_id;
 mod dirs;
 mod disk_tool;
+mod firmware_image;
+mod firmware_library;
+mod hotplug;
 mod log_entries;
 mod open_ocd_task;
 mod operator_tool;
+mod option_bytes;
+mod progress;
+mod run_history;
 mod stackfile_config;
+mod usb_ids;
 mod utils;
 
 mod ui;
@@ -13,9 +13,13 @@ use async_io::Timer;
 use iced::futures::{channel::mpsc::Sender, SinkExt};
 
 use crate::{
-    dirs,
+    device_id::{parse_memory_dumps, parse_tap_idcode, DeviceId, SwdTarget, TargetSettings},
+    dirs, disk_tool,
+    firmware_image::{format_ranges, mismatch_ranges, FirmwareImage, ERASED_BYTE},
     log_entries::{LogEntries, LogType},
+    option_bytes::{self, F1Protection, OptionBytes, RdpLevel, F1_FLASH_OBR, F1_FLASH_WRPR},
     ui::messages::{TabDaplinkMessage, TabWsMessage, WithLogMessage},
+    usb_ids::KnownProbe,
     ProcessResult,
 };
 
@@ -23,28 +27,41 @@ pub const UNLOCK_SCRIPT_FILENAME: &str = "f1x-unlock.cfg";
 pub const ERASE_SCRIPT_FILENAME: &str = "f1x-mass-erase.cfg";
 pub const FLASH_SCRIPT_FILENAME: &str = "f1x-flash.cfg";
 pub const WB55_CONFIG: &str = "wb5x.cfg";
-
-pub async fn unlock_target() -> Result<ProcessResult, String> {
+pub const READ_ID_SCRIPT_FILENAME: &str = "f1x-read-id.cfg";
+pub const READ_OPTIONS_SCRIPT_FILENAME: &str = "f1x-read-options.cfg";
+pub const LOCK_SCRIPT_FILENAME: &str = "f1x-lock.cfg";
+/// Connection to the interface MCU only, the commands are given with `-c`.
+pub const F1_TARGET_CONFIG: &str = "f1x-target.cfg";
+/// Where raw binaries are programmed on the STM32F1.
+const FLASH_BASE_ADDRESS: u32 = 0x0800_0000;
+
+pub async fn unlock_target(settings: TargetSettings) -> Result<ProcessResult, String> {
     let script_folder: &Path = &dirs::get_configs_dir()?;
     let path_script = script_folder.join(UNLOCK_SCRIPT_FILENAME);
 
     let mut command = Command::new("openocd");
+    command.args(settings.openocd_args());
     command.args(&["-f", &format!("{}", path_script.to_str().unwrap())]);
 
     Ok(run_command_sender::<TabDaplinkMessage>(&mut command, None).await?)
 }
 
-pub async fn erase_target() -> Result<ProcessResult, String> {
+pub async fn erase_target(settings: TargetSettings) -> Result<ProcessResult, String> {
     let script_folder: &Path = &dirs::get_configs_dir()?;
     let path_script = script_folder.join(ERASE_SCRIPT_FILENAME);
 
     let mut command = Command::new("openocd");
+    command.args(settings.openocd_args());
     command.args(&["-f", &format!("{}", path_script.to_str().unwrap())]);
 
     Ok(run_command_sender::<TabDaplinkMessage>(&mut command, None).await?)
 }
 
-pub async fn flash_target(bin_path: PathBuf) -> Result<ProcessResult, String> {
+pub async fn flash_target(
+    bin_path: PathBuf,
+    settings: TargetSettings,
+    sender: &mut Sender<TabDaplinkMessage>,
+) -> Result<ProcessResult, String> {
     let script_folder: &Path = &dirs::get_configs_dir()?;
     let path_script = script_folder.join(FLASH_SCRIPT_FILENAME);
 
@@ -52,7 +69,12 @@ pub async fn flash_target(bin_path: PathBuf) -> Result<ProcessResult, String> {
         return Err("The firmware path is not a file.".into());
     }
 
-    match fs::copy(bin_path, dirs::get_tmp_dir()?.join("bootloader")) {
+    // The script programs a raw binary: HEX, ELF and S-record files are
+    // flattened and programmed at their own address.
+    let image = FirmwareImage::load(&bin_path)?;
+    let address = image.base_address.unwrap_or(FLASH_BASE_ADDRESS);
+
+    match fs::write(dirs::get_tmp_dir()?.join("bootloader"), &image.data) {
         Ok(_) => (),
         Err(e) => return Err(format!("Failed to copy bootloader file ({e}")),
     };
@@ -63,14 +85,353 @@ pub async fn flash_target(bin_path: PathBuf) -> Result<ProcessResult, String> {
         .map_err(|_| "Failed to convert tmp_dir to string.")?;
 
     let mut command = Command::new("openocd");
+    command.args(settings.openocd_args());
     command.args(&[
         "-s",
         &tmp_dir_string,
+        "-c",
+        &format!("set FLASH_ADDRESS 0x{address:08X}"),
         "-f",
         &format!("{}", path_script.to_str().unwrap()),
     ]);
+    for dump in readback_commands(&image, FLASH_BASE_ADDRESS)? {
+        command.args(["-c", &dump]);
+    }
+    command.args(["-c", "reset run", "-c", "exit"]);
 
-    Ok(run_command_sender::<TabDaplinkMessage>(&mut command, None).await?)
+    run_command_sender(&mut command, Some(sender)).await
+}
+
+/// Reads the unique ID, flash size and IDCODE of the chip behind the probe.
+/// Only the probe of `settings` is used, any SW-DP IDCODE is accepted.
+pub async fn read_device_id(
+    target: SwdTarget,
+    settings: TargetSettings,
+) -> Result<DeviceId, String> {
+    let mut command = Command::new("openocd");
+    match target {
+        SwdTarget::Stm32F1 => {
+            let script_folder: &Path = &dirs::get_configs_dir()?;
+            let path_script = script_folder.join(READ_ID_SCRIPT_FILENAME);
+            command.args(settings.probe.openocd_args());
+            command.args(["-f", path_script.to_str().unwrap_or_default()]);
+        }
+        SwdTarget::Stm32Wb => {
+            command.args(["-f", WB55_CONFIG, "-c", "init"]);
+            for read in target.read_commands() {
+                command.args(["-c", &read]);
+            }
+            command.args(["-c", "exit"]);
+        }
+    }
+
+    let result = run_command_sender::<TabDaplinkMessage>(&mut command, None).await?;
+    let lines = output_lines(&result);
+
+    DeviceId::parse(target, lines.iter().map(|l| l.as_str())).map_err(|e| {
+        format!(
+            "Failed to read the device ID ({})",
+            last_error(&lines).unwrap_or(e)
+        )
+    })
+}
+
+/// True if an interface MCU answers on the SWD bus of `probe`, whatever its
+/// protection: only the debug port IDCODE is looked at.
+pub async fn detect_target(probe: KnownProbe) -> bool {
+    let script_folder = match dirs::get_configs_dir() {
+        Ok(folder) => folder,
+        Err(_) => return false,
+    };
+
+    let mut command = Command::new("openocd");
+    command.args(probe.openocd_args());
+    command.args([
+        "-f",
+        script_folder
+            .join(READ_ID_SCRIPT_FILENAME)
+            .to_str()
+            .unwrap_or_default(),
+    ]);
+
+    match run_command_sender::<TabDaplinkMessage>(&mut command, None).await {
+        Ok(result) => {
+            let lines = output_lines(&result);
+            let lines: Vec<&str> = lines.iter().map(|l| l.as_str()).collect();
+            parse_tap_idcode(&lines).is_some()
+        }
+        Err(_) => false,
+    }
+}
+
+/// Reads the read-out and write protections from the option bytes.
+pub async fn read_protection(settings: TargetSettings) -> Result<F1Protection, String> {
+    let script_folder: &Path = &dirs::get_configs_dir()?;
+    let path_script = script_folder.join(READ_OPTIONS_SCRIPT_FILENAME);
+
+    let mut command = Command::new("openocd");
+    command.args(settings.openocd_args());
+    command.args(["-f", path_script.to_str().unwrap_or_default()]);
+
+    let result = run_command_sender::<TabDaplinkMessage>(&mut command, None).await?;
+    let lines = output_lines(&result);
+
+    let dumps = parse_memory_dumps(lines.iter().map(|l| l.as_str()));
+    let register = |address: u32, name: &str| {
+        dumps
+            .get(&address)
+            .and_then(|values| values.first())
+            .copied()
+            .ok_or_else(|| {
+                format!(
+                    "Failed to read the option bytes ({})",
+                    last_error(&lines).unwrap_or_else(|| format!("{name} was not read"))
+                )
+            })
+    };
+
+    Ok(F1Protection {
+        rdp: RdpLevel::from_f1_obr(register(F1_FLASH_OBR, "FLASH_OBR")?),
+        wrpr: register(F1_FLASH_WRPR, "FLASH_WRPR")?,
+    })
+}
+
+pub async fn lock_target(settings: TargetSettings) -> Result<ProcessResult, String> {
+    let script_folder: &Path = &dirs::get_configs_dir()?;
+    let path_script = script_folder.join(LOCK_SCRIPT_FILENAME);
+
+    let mut command = Command::new("openocd");
+    command.args(settings.openocd_args());
+    command.args(["-f", path_script.to_str().unwrap_or_default()]);
+
+    run_command_sender::<TabDaplinkMessage>(&mut command, None).await
+}
+
+/// Runs `commands` once connected to `target`.
+async fn run_on_target(
+    target: SwdTarget,
+    settings: TargetSettings,
+    commands: &[String],
+) -> Result<ProcessResult, String> {
+    let mut command = Command::new("openocd");
+    if target == SwdTarget::Stm32F1 {
+        command.args(settings.openocd_args());
+    }
+    command.args([
+        "-f",
+        match target {
+            SwdTarget::Stm32F1 => F1_TARGET_CONFIG,
+            SwdTarget::Stm32Wb => WB55_CONFIG,
+        },
+        "-c",
+        "init",
+    ]);
+    for c in commands {
+        command.args(["-c", c]);
+    }
+    command.args(["-c", "exit"]);
+
+    run_command_sender::<TabDaplinkMessage>(&mut command, None).await
+}
+
+pub async fn read_option_bytes(
+    target: SwdTarget,
+    settings: TargetSettings,
+) -> Result<OptionBytes, String> {
+    let registers = option_bytes::registers(target);
+    let reads: Vec<String> = registers.iter().map(|r| format!("mdw 0x{r:08X}")).collect();
+
+    let result = run_on_target(target, settings, &reads).await?;
+    let lines = output_lines(&result);
+    let dumps = parse_memory_dumps(lines.iter().map(|l| l.as_str()));
+
+    let values: Vec<u32> = registers
+        .iter()
+        .filter_map(|r| dumps.get(r).and_then(|v| v.first()).copied())
+        .collect();
+
+    OptionBytes::new(target, values).map_err(|e| {
+        format!(
+            "Failed to read the option bytes ({})",
+            last_error(&lines).unwrap_or(e)
+        )
+    })
+}
+
+/// Runs the commands given by `OptionBytes::write_commands`, target halted.
+pub async fn write_option_bytes(
+    target: SwdTarget,
+    settings: TargetSettings,
+    commands: Vec<String>,
+) -> Result<ProcessResult, String> {
+    let mut all = vec!["reset halt".to_string()];
+    all.extend(commands);
+
+    run_on_target(target, settings, &all).await
+}
+
+/// Dumps the whole flash of the interface MCU to `path`.
+pub async fn backup_flash(
+    settings: TargetSettings,
+    flash_size_kb: u16,
+    path: PathBuf,
+) -> Result<ProcessResult, String> {
+    let commands = [
+        "reset halt".to_string(),
+        dump_command(&path, FLASH_BASE_ADDRESS, u32::from(flash_size_kb) * 1024),
+    ];
+
+    run_on_target(SwdTarget::Stm32F1, settings, &commands).await
+}
+
+fn dump_command(path: &Path, address: u32, size: u32) -> String {
+    // Braces keep Tcl away from the backslashes of Windows paths
+    format!(
+        "dump_image {{{}}} 0x{address:08X} 0x{size:X}",
+        path.to_str().unwrap_or_default()
+    )
+}
+
+fn readback_path(index: usize) -> Result<PathBuf, String> {
+    Ok(dirs::get_tmp_dir()?.join(format!("readback-{index}.bin")))
+}
+
+/// Address ranges of `image`, raw binaries being programmed at `default_base`.
+fn image_ranges(image: &FirmwareImage, default_base: u32) -> Vec<(u32, u32)> {
+    let base = image.base_address.unwrap_or(default_base);
+    if image.ranges.is_empty() {
+        vec![(base, base + image.data.len() as u32)]
+    } else {
+        image.ranges.clone()
+    }
+}
+
+/// `dump_image` commands to run in the programming session, before its final
+/// reset, so that `verify_image` can compare the files afterwards. Restarting
+/// the chip in between would interrupt what the new program is doing.
+fn readback_commands(image: &FirmwareImage, default_base: u32) -> Result<Vec<String>, String> {
+    let mut commands = Vec::new();
+    for (i, (start, end)) in image_ranges(image, default_base).into_iter().enumerate() {
+        let path = readback_path(i)?;
+        let _ = fs::remove_file(&path);
+        commands.push(dump_command(&path, start, end - start));
+    }
+    Ok(commands)
+}
+
+/// Reads every `(start, end)` range in a single OpenOCD session, core halted.
+async fn read_memory(
+    target: SwdTarget,
+    settings: TargetSettings,
+    ranges: &[(u32, u32)],
+) -> Result<Vec<Vec<u8>>, String> {
+    let paths = (0..ranges.len())
+        .map(readback_path)
+        .collect::<Result<Vec<PathBuf>, String>>()?;
+
+    let mut commands = vec!["halt".to_string()];
+    for (path, (start, end)) in paths.iter().zip(ranges) {
+        let _ = fs::remove_file(path);
+        commands.push(dump_command(path, *start, end - start));
+    }
+    commands.push("resume".to_string());
+
+    let result = run_on_target(target, settings, &commands).await?;
+    let lines = output_lines(&result);
+
+    let mut data = Vec::with_capacity(ranges.len());
+    for (path, (start, end)) in paths.iter().zip(ranges) {
+        match (result.code, fs::read(path)) {
+            (Some(0), Ok(bytes)) if bytes.len() == (end - start) as usize => data.push(bytes),
+            _ => {
+                return Err(format!(
+                    "Failed to read 0x{start:08X}-0x{:08X} ({})",
+                    end - 1,
+                    last_error(&lines).unwrap_or_else(|| "no data".into())
+                ))
+            }
+        }
+        let _ = fs::remove_file(path);
+    }
+
+    Ok(data)
+}
+
+/// Checks that the whole flash of the interface MCU reads as erased.
+pub async fn blank_check(settings: TargetSettings, flash_size_kb: u16) -> Result<String, String> {
+    let size = u32::from(flash_size_kb) * 1024;
+    let data = read_memory(
+        SwdTarget::Stm32F1,
+        settings,
+        &[(FLASH_BASE_ADDRESS, FLASH_BASE_ADDRESS + size)],
+    )
+    .await?
+    .remove(0);
+
+    let not_blank = mismatch_ranges(&vec![ERASED_BYTE; data.len()], &data, FLASH_BASE_ADDRESS);
+    if not_blank.is_empty() {
+        Ok(format!("{flash_size_kb} KiB blank"))
+    } else {
+        Err(format!("Not blank: {}", format_ranges(&not_blank)))
+    }
+}
+
+/// Compares every range of `image_path` with what the programming session
+/// read back (see `readback_commands`).
+pub async fn verify_image(image_path: PathBuf) -> Result<String, String> {
+    let image = FirmwareImage::load(&image_path)?;
+    let base = image.base_address.unwrap_or(FLASH_BASE_ADDRESS);
+    let ranges = image_ranges(&image, FLASH_BASE_ADDRESS);
+
+    let mut mismatches = Vec::new();
+    for (i, (start, end)) in ranges.iter().enumerate() {
+        let path = readback_path(i)?;
+        let actual = match fs::read(&path) {
+            Ok(bytes) if bytes.len() == (end - start) as usize => bytes,
+            _ => return Err(format!("0x{start:08X}-0x{:08X} was not read back", end - 1)),
+        };
+        let _ = fs::remove_file(&path);
+
+        let expected = &image.data[(start - base) as usize..(end - base) as usize];
+        mismatches.extend(mismatch_ranges(expected, &actual, *start));
+    }
+
+    if mismatches.is_empty() {
+        Ok(format!(
+            "{} bytes match, CRC32 0x{:08X}",
+            image.data.len(),
+            disk_tool::crc32(&image.data)
+        ))
+    } else {
+        Err(format!("Mismatch: {}", format_ranges(&mismatches)))
+    }
+}
+
+/// Path of a file given to `flash_wb55`, found like OpenOCD does.
+pub fn wb55_image_path(file: &str) -> Result<PathBuf, String> {
+    let tmp = dirs::get_tmp_dir()?.join(file);
+    if tmp.is_file() {
+        return Ok(tmp);
+    }
+    Ok(dirs::get_wireless_stack_dir()?.join(file))
+}
+
+/// OpenOCD output collected by `run_command_sender`, without the indent.
+fn output_lines(result: &ProcessResult) -> Vec<String> {
+    result
+        .log
+        .as_deque()
+        .iter()
+        .filter_map(|entry| match entry {
+            LogType::Info(line) => Some(line.trim().to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The last OpenOCD error says more than a missing value.
+fn last_error(lines: &[String]) -> Option<String> {
+    lines.iter().rev().find(|l| l.contains("Error")).cloned()
 }
 
 pub fn is_installed() -> Result<bool, String> {
@@ -86,17 +447,14 @@ pub async fn flash_wb55(
     file: &str,
     sender: &mut Sender<TabWsMessage>,
 ) -> Result<ProcessResult, String> {
+    let image = FirmwareImage::load(&wb55_image_path(file)?)?;
+
     let mut command = Command::new("openocd");
-    command.args(&[
-        "-f",
-        WB55_CONFIG,
-        "-c",
-        &format!("program {} verify reset", file),
-        "-c",
-        "reset run",
-        "-c",
-        "exit",
-    ]);
+    command.args(["-f", WB55_CONFIG, "-c", &format!("program {} verify", file)]);
+    for dump in readback_commands(&image, FLASH_BASE_ADDRESS)? {
+        command.args(["-c", &dump]);
+    }
+    command.args(["-c", "reset run", "-c", "exit"]);
 
     run_command_sender(&mut command, Some(sender)).await
 }
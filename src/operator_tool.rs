@@ -46,3 +46,9 @@ pub fn upgrade_status_string(status_code: u32) -> &'static str {
         "Unknown status code"
     }
 }
+
+/// FUS or wireless stack upgrade in progress. FUS tells nothing about how
+/// far it went, only that it is back to idle once done.
+pub fn upgrade_status_is_ongoing(status_code: u32) -> bool {
+    matches!(status_code, 0x10..=0x2F)
+}
@@ -0,0 +1,469 @@
+use crate::device_id::SwdTarget;
+
+/// FLASH_OBR of the STM32F1: the option bytes as loaded at the last reset.
+pub const F1_FLASH_OBR: u32 = 0x4002_201C;
+/// FLASH_WRPR of the STM32F1: a cleared bit write-protects its pages.
+pub const F1_FLASH_WRPR: u32 = 0x4002_2020;
+
+const WB_FLASH_OPTR: u32 = 0x5800_4020;
+const WB_FLASH_WRP1AR: u32 = 0x5800_402C;
+const WB_FLASH_WRP1BR: u32 = 0x5800_4030;
+const WB_FLASH_SFR: u32 = 0x5800_4080;
+/// FLASH_OPTR offset from the flash registers, for `option_write`.
+const WB_OPTR_OFFSET: u32 = 0x20;
+
+const FLASH_BASE: u32 = 0x0800_0000;
+const WB_PAGE_SIZE: u32 = 4096;
+
+/// Read-out protection level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RdpLevel {
+    /// No protection.
+    Level0,
+    /// Flash not readable over SWD; removing it erases the flash.
+    Level1,
+    /// Debug disabled for good.
+    Level2,
+}
+
+impl RdpLevel {
+    /// The STM32F1 only has one protection level, flagged by RDPRT.
+    pub fn from_f1_obr(obr: u32) -> Self {
+        if obr & 0b10 != 0 {
+            RdpLevel::Level1
+        } else {
+            RdpLevel::Level0
+        }
+    }
+
+    pub fn from_wb_rdp(rdp: u32) -> Self {
+        match rdp {
+            0xAA => RdpLevel::Level0,
+            0xCC => RdpLevel::Level2,
+            _ => RdpLevel::Level1,
+        }
+    }
+}
+
+/// What `stm32f1x unlock` removes: the read-out and the write protections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct F1Protection {
+    pub rdp: RdpLevel,
+    pub wrpr: u32,
+}
+
+impl F1Protection {
+    pub fn is_write_protected(&self) -> bool {
+        self.wrpr != u32::MAX
+    }
+}
+
+impl std::fmt::Display for RdpLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            RdpLevel::Level0 => "level 0",
+            RdpLevel::Level1 => "level 1",
+            RdpLevel::Level2 => "level 2",
+        })
+    }
+}
+
+/// One field of the option bytes, `width` bits at `shift` in `registers[register]`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct OptionField {
+    pub name: &'static str,
+    pub description: &'static str,
+    register: usize,
+    shift: u32,
+    width: u32,
+    /// Only single-bit fields are editable.
+    pub editable: bool,
+}
+
+const fn field(
+    name: &'static str,
+    description: &'static str,
+    register: usize,
+    shift: u32,
+    width: u32,
+    editable: bool,
+) -> OptionField {
+    OptionField {
+        name,
+        description,
+        register,
+        shift,
+        width,
+        editable,
+    }
+}
+
+const F1_FIELDS: [OptionField; 7] = [
+    field(
+        "RDP",
+        "Read-out protection: flash not readable over SWD. Removing it erases the flash.",
+        0,
+        1,
+        1,
+        true,
+    ),
+    field(
+        "WDG_SW",
+        "1: watchdog started by software, 0: watchdog always on (hardware)",
+        0,
+        2,
+        1,
+        true,
+    ),
+    field(
+        "nRST_STOP",
+        "0: reset when entering Stop mode",
+        0,
+        3,
+        1,
+        true,
+    ),
+    field(
+        "nRST_STDBY",
+        "0: reset when entering Standby mode",
+        0,
+        4,
+        1,
+        true,
+    ),
+    field("Data0", "User data byte 0", 0, 10, 8, false),
+    field("Data1", "User data byte 1", 0, 18, 8, false),
+    field(
+        "WRP",
+        "Write protection, one bit per 4 KiB (0: protected)",
+        1,
+        0,
+        32,
+        false,
+    ),
+];
+
+const WB_FIELDS: [OptionField; 17] = [
+    field(
+        "RDP",
+        "Read-out protection (0xAA: level 0, 0xCC: level 2, other: level 1)",
+        0,
+        0,
+        8,
+        false,
+    ),
+    field(
+        "ESE",
+        "1: system security enabled (set by FUS)",
+        0,
+        8,
+        1,
+        false,
+    ),
+    field("BOR_LEV", "Brown-out reset threshold", 0, 9, 3, false),
+    field(
+        "nRST_STOP",
+        "0: reset when entering Stop mode",
+        0,
+        12,
+        1,
+        true,
+    ),
+    field(
+        "nRST_STDBY",
+        "0: reset when entering Standby mode",
+        0,
+        13,
+        1,
+        true,
+    ),
+    field(
+        "nRST_SHDW",
+        "0: reset when entering Shutdown mode",
+        0,
+        14,
+        1,
+        true,
+    ),
+    field(
+        "IWDG_SW",
+        "1: independent watchdog started by software, 0: by hardware",
+        0,
+        16,
+        1,
+        true,
+    ),
+    field(
+        "WWDG_SW",
+        "1: window watchdog started by software, 0: by hardware",
+        0,
+        19,
+        1,
+        true,
+    ),
+    field(
+        "nBOOT1",
+        "Boot from SRAM1 (0) or system memory (1) when BOOT0 is selected",
+        0,
+        23,
+        1,
+        true,
+    ),
+    field(
+        "nSWBOOT0",
+        "0: boot selected by the nBOOT0 option bit, 1: by the BOOT0 pin",
+        0,
+        26,
+        1,
+        true,
+    ),
+    field(
+        "nBOOT0",
+        "Boot selection when nSWBOOT0 is 0 (1: main flash)",
+        0,
+        27,
+        1,
+        true,
+    ),
+    field(
+        "WRP1A_STRT",
+        "First write protected page of area A",
+        1,
+        0,
+        8,
+        false,
+    ),
+    field(
+        "WRP1A_END",
+        "Last write protected page of area A",
+        1,
+        16,
+        8,
+        false,
+    ),
+    field(
+        "WRP1B_STRT",
+        "First write protected page of area B",
+        2,
+        0,
+        8,
+        false,
+    ),
+    field(
+        "WRP1B_END",
+        "Last write protected page of area B",
+        2,
+        16,
+        8,
+        false,
+    ),
+    field(
+        "SFSA",
+        "Secure flash start page: FUS and the wireless stack live from there to the end",
+        3,
+        0,
+        8,
+        false,
+    ),
+    field("FSD", "0: secure flash enabled", 3, 8, 1, false),
+];
+
+/// Registers holding the option bytes of `target`, in `OptionField::register` order.
+pub fn registers(target: SwdTarget) -> &'static [u32] {
+    match target {
+        SwdTarget::Stm32F1 => &[F1_FLASH_OBR, F1_FLASH_WRPR],
+        SwdTarget::Stm32Wb => &[
+            WB_FLASH_OPTR,
+            WB_FLASH_WRP1AR,
+            WB_FLASH_WRP1BR,
+            WB_FLASH_SFR,
+        ],
+    }
+}
+
+/// The option bytes of a chip, as read from its flash registers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionBytes {
+    pub target: SwdTarget,
+    values: Vec<u32>,
+}
+
+impl OptionBytes {
+    pub fn new(target: SwdTarget, values: Vec<u32>) -> Result<Self, String> {
+        if values.len() != registers(target).len() {
+            return Err("Some option byte registers were not read".into());
+        }
+        Ok(Self { target, values })
+    }
+
+    pub fn fields(&self) -> &'static [OptionField] {
+        match self.target {
+            SwdTarget::Stm32F1 => &F1_FIELDS,
+            SwdTarget::Stm32Wb => &WB_FIELDS,
+        }
+    }
+
+    pub fn get(&self, field: &OptionField) -> u32 {
+        let mask = u32::MAX >> (32 - field.width);
+        (self.values[field.register] >> field.shift) & mask
+    }
+
+    pub fn set(&mut self, field: &OptionField, value: u32) {
+        let mask = (u32::MAX >> (32 - field.width)) << field.shift;
+        let register = &mut self.values[field.register];
+        *register = (*register & !mask) | ((value << field.shift) & mask);
+    }
+
+    /// Value as shown in the panel.
+    pub fn decode(&self, field: &OptionField) -> String {
+        let value = self.get(field);
+        match (self.target, field.name) {
+            (SwdTarget::Stm32F1, "RDP") => RdpLevel::from_f1_obr(value << 1).to_string(),
+            (SwdTarget::Stm32Wb, "RDP") => {
+                format!("0x{value:02X} ({})", RdpLevel::from_wb_rdp(value))
+            }
+            (SwdTarget::Stm32Wb, "SFSA") => format!(
+                "0x{value:02X} (0x{:08X})",
+                FLASH_BASE + value * WB_PAGE_SIZE
+            ),
+            _ if field.width == 1 => value.to_string(),
+            _ => format!(
+                "0x{value:0width$X}",
+                width = field.width.div_ceil(4) as usize
+            ),
+        }
+    }
+
+    fn rdp_level(&self) -> RdpLevel {
+        match self.target {
+            SwdTarget::Stm32F1 => RdpLevel::from_f1_obr(self.values[0]),
+            SwdTarget::Stm32Wb => RdpLevel::from_wb_rdp(self.values[0] & 0xFF),
+        }
+    }
+
+    /// True if writing `edited` brings the read-out protection back to
+    /// level 0, which mass-erases the flash.
+    pub fn erases_flash(&self, edited: &OptionBytes) -> bool {
+        self.rdp_level() != RdpLevel::Level0 && edited.rdp_level() == RdpLevel::Level0
+    }
+
+    /// `name: old -> new` for every field that differs from `other`.
+    pub fn diff(&self, other: &OptionBytes) -> Vec<String> {
+        self.fields()
+            .iter()
+            .filter(|f| self.get(f) != other.get(f))
+            .map(|f| format!("{}: {} -> {}", f.name, self.decode(f), other.decode(f)))
+            .collect()
+    }
+
+    /// OpenOCD commands turning `self` into `edited`.
+    pub fn write_commands(&self, edited: &OptionBytes) -> Vec<String> {
+        let mut commands = Vec::new();
+
+        match self.target {
+            SwdTarget::Stm32F1 => {
+                let flag = |name: &str| {
+                    edited
+                        .fields()
+                        .iter()
+                        .find(|f| f.name == name)
+                        .map(|f| edited.get(f) == 1)
+                        .unwrap_or_default()
+                };
+                let rdp_changed = self.values[0] & 0b10 != edited.values[0] & 0b10;
+                let user_mask = 0b1_1100;
+
+                if self.values[0] & user_mask != edited.values[0] & user_mask {
+                    commands.push(format!(
+                        "stm32f1x options_write 0 {} {} {}",
+                        if flag("WDG_SW") { "SWWDG" } else { "HWWDG" },
+                        if flag("nRST_STDBY") {
+                            "NORSTSTNDBY"
+                        } else {
+                            "RSTSTNDBY"
+                        },
+                        if flag("nRST_STOP") {
+                            "NORSTSTOP"
+                        } else {
+                            "RSTSTOP"
+                        },
+                    ));
+                }
+                if rdp_changed {
+                    // Both rewrite every option byte from FLASH_OBR, which
+                    // only shows the new USER bits once reloaded at reset.
+                    if !commands.is_empty() {
+                        commands.push("reset halt".into());
+                    }
+                    commands.push(if flag("RDP") {
+                        "stm32f1x lock 0".into()
+                    } else {
+                        "stm32f1x unlock 0".into()
+                    });
+                }
+                if !commands.is_empty() {
+                    // The option bytes are only loaded at reset
+                    commands.push("reset run".into());
+                }
+            }
+            SwdTarget::Stm32Wb => {
+                let mask = self.values[0] ^ edited.values[0];
+                if mask != 0 {
+                    commands.push(format!(
+                        "stm32l4x option_write 0 0x{WB_OPTR_OFFSET:02x} 0x{:08x} 0x{mask:08x}",
+                        edited.values[0]
+                    ));
+                    // Reloads the option bytes, which resets the chip
+                    commands.push("stm32l4x option_load 0".into());
+                }
+            }
+        }
+
+        commands
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wb_fields_are_decoded_and_written() {
+        let read = OptionBytes::new(
+            SwdTarget::Stm32Wb,
+            vec![0x3DFF_F1AA, 0x0000_00FF, 0x0000_00FF, 0x0000_2FC4],
+        )
+        .unwrap();
+        let sfsa = &read.fields()[15];
+        assert_eq!(read.decode(sfsa), "0xC4 (0x080C4000)");
+
+        let mut edited = read.clone();
+        let n_swboot0 = &edited.fields()[9];
+        edited.set(n_swboot0, 0);
+
+        assert_eq!(read.diff(&edited), ["nSWBOOT0: 1 -> 0"]);
+        assert_eq!(
+            read.write_commands(&edited)[0],
+            "stm32l4x option_write 0 0x20 0x39fff1aa 0x04000000"
+        );
+    }
+
+    #[test]
+    fn f1_unlock_after_user_change_is_reset_in_between() {
+        let read = OptionBytes::new(SwdTarget::Stm32F1, vec![0x0000_001E, 0xFFFF_FFFF]).unwrap();
+        let mut edited = read.clone();
+        edited.set(&edited.fields()[0], 0);
+        edited.set(&edited.fields()[1], 0);
+
+        assert!(read.erases_flash(&edited));
+        assert_eq!(
+            read.write_commands(&edited),
+            [
+                "stm32f1x options_write 0 HWWDG NORSTSTNDBY NORSTSTOP",
+                "reset halt",
+                "stm32f1x unlock 0",
+                "reset run"
+            ]
+        );
+    }
+}
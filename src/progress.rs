@@ -0,0 +1,194 @@
+use std::time::{Duration, Instant};
+
+use crate::operator_tool;
+
+/// Where a long sequence stands: the current step out of all, how far the
+/// operation of that step went and what is left to wait.
+#[derive(Debug)]
+pub struct Progress {
+    /// 1-based, 0 before the first step.
+    step: usize,
+    total: Option<usize>,
+    name: String,
+    /// Done part of the current operation, when the tools tell.
+    fraction: Option<f32>,
+    detail: String,
+    /// An upgrade was reported ongoing during the current step.
+    fus_ongoing_seen: bool,
+    step_started: Instant,
+    /// Duration of each step during a previous identical run, in ms.
+    reference: Vec<u64>,
+}
+
+impl Progress {
+    pub fn new(total: Option<usize>, reference: Vec<u64>) -> Self {
+        Self {
+            step: 0,
+            total: total.or((!reference.is_empty()).then_some(reference.len())),
+            name: String::new(),
+            fraction: None,
+            detail: String::new(),
+            fus_ongoing_seen: false,
+            step_started: Instant::now(),
+            reference,
+        }
+    }
+
+    pub fn begin_step(&mut self, name: &str) {
+        self.set_step(self.step + 1, name);
+    }
+
+    /// For sequences that go back to an earlier step (FUS upgrade loop).
+    pub fn set_step(&mut self, step: usize, name: &str) {
+        self.step = step;
+        self.name = name.to_string();
+        self.fraction = None;
+        self.detail.clear();
+        self.fus_ongoing_seen = false;
+        self.step_started = Instant::now();
+        if let Some(total) = self.total {
+            self.total = Some(total.max(step));
+        }
+    }
+
+    pub fn fraction(&self) -> Option<f32> {
+        self.fraction
+    }
+
+    pub fn set_fraction(&mut self, fraction: f32, detail: String) {
+        self.fraction = Some(fraction.clamp(0.0, 1.0));
+        self.detail = detail;
+    }
+
+    /// Milestones printed by the OpenOCD `program` command.
+    pub fn feed_openocd(&mut self, line: &str) {
+        let line = line.trim();
+        let fraction = if line.contains("** Programming Started **") {
+            0.05
+        } else if line.starts_with("wrote ") || line.contains("** Programming Finished **") {
+            0.6
+        } else if line.contains("** Verify Started **") {
+            0.65
+        } else if line.starts_with("verified ") || line.contains("** Verified OK **") {
+            1.0
+        } else {
+            return;
+        };
+
+        let detail = if line.starts_with("wrote ") || line.starts_with("verified ") {
+            // "wrote 24576 bytes from file x.hex in 1.2s (20.0 KiB/s)"
+            line.split(" from file").next().unwrap_or(line).to_string()
+        } else {
+            line.trim_matches(|c| c == '*' || c == ' ').to_string()
+        };
+        self.set_fraction(fraction, detail);
+    }
+
+    /// `status` of the operator during a FUS or wireless stack upgrade: no
+    /// fraction while it is ongoing, done once idle again after that.
+    pub fn feed_fus_status(&mut self, status: u32) {
+        let detail = operator_tool::upgrade_status_string(status).to_string();
+        if operator_tool::upgrade_status_is_ongoing(status) {
+            self.fus_ongoing_seen = true;
+            self.fraction = None;
+            self.detail = detail;
+        } else if status == 0 && self.fus_ongoing_seen {
+            self.set_fraction(1.0, detail);
+        } else {
+            self.detail = detail;
+        }
+    }
+
+    /// Left to wait, from the progress of the current operation or the
+    /// previous run, plus the next steps of the previous run.
+    fn remaining(&self, elapsed: Duration) -> Option<Duration> {
+        let current = match self.fraction {
+            Some(f) if f >= 1.0 => Some(Duration::ZERO),
+            Some(f) if f > 0.02 => Some(elapsed.mul_f32((1.0 - f) / f)),
+            _ => self
+                .step
+                .checked_sub(1)
+                .and_then(|i| self.reference.get(i))
+                .map(|ms| Duration::from_millis(*ms).saturating_sub(elapsed)),
+        };
+        let next: u64 = self.reference.iter().skip(self.step).sum();
+
+        match current {
+            Some(current) => Some(current + Duration::from_millis(next)),
+            None if next > 0 => Some(Duration::from_millis(next)),
+            None => None,
+        }
+    }
+
+    /// `Step 3/8: Flash bootloader`
+    pub fn title(&self) -> String {
+        match self.total {
+            Some(total) => format!("Step {}/{total}: {}", self.step, self.name),
+            None => format!("Step {}: {}", self.step, self.name),
+        }
+    }
+
+    /// `60 % - wrote 24576 bytes - about 12 s left`
+    pub fn status(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(fraction) = self.fraction {
+            parts.push(format!("{:.0} %", fraction * 100.0));
+        }
+        if !self.detail.is_empty() {
+            parts.push(self.detail.clone());
+        }
+        if let Some(remaining) = self.remaining(self.step_started.elapsed()) {
+            parts.push(format!("about {} left", format_remaining(remaining)));
+        }
+        parts.join(" - ")
+    }
+}
+
+fn format_remaining(remaining: Duration) -> String {
+    let secs = remaining.as_secs();
+    if secs < 60 {
+        format!("{secs} s")
+    } else {
+        format!("{} min {:02} s", secs / 60, secs % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remaining_time_uses_fraction_then_reference() {
+        let mut progress = Progress::new(None, vec![1000, 4000, 2000]);
+        progress.begin_step("Erase");
+        progress.begin_step("Flash");
+        assert_eq!(progress.title(), "Step 2/3: Flash");
+
+        // Previous run: 4 s for this step, 2 s for the next one
+        assert_eq!(
+            progress.remaining(Duration::from_secs(1)),
+            Some(Duration::from_secs(5))
+        );
+
+        progress.feed_openocd("** Programming Started **");
+        progress.feed_openocd("wrote 24576 bytes from file bootloader in 1.2s (20.0 KiB/s)");
+        assert_eq!(progress.fraction(), Some(0.6));
+        assert_eq!(progress.detail, "wrote 24576 bytes");
+        // 60 % in 3 s: 2 s left, plus 2 s for the next step
+        let remaining = progress.remaining(Duration::from_secs(3)).unwrap();
+        assert!((remaining.as_secs_f32() - 4.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn fus_idle_is_done_only_after_ongoing() {
+        let mut progress = Progress::new(Some(2), Vec::new());
+        progress.begin_step("Install wireless stack");
+
+        progress.feed_fus_status(0x00);
+        assert_eq!(progress.fraction(), None);
+        progress.feed_fus_status(0x12);
+        assert_eq!(progress.fraction(), None);
+        progress.feed_fus_status(0x00);
+        assert_eq!(progress.fraction(), Some(1.0));
+    }
+}
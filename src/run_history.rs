@@ -0,0 +1,305 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{device_id::DeviceId, dirs};
+
+/// One JSON record per line, only ever appended to.
+const HISTORY_FILE: &str = "history.jsonl";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageHash {
+    /// "Bootloader", "Firmware" or "User program".
+    pub label: String,
+    pub file_name: String,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepRecord {
+    pub name: String,
+    pub passed: bool,
+    pub duration_ms: u64,
+}
+
+/// What happened to one board.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunRecord {
+    /// Start of the run, seconds since the Unix epoch (UTC).
+    pub timestamp: u64,
+    pub profile: String,
+    pub mode: String,
+    pub images: Vec<ImageHash>,
+    pub unique_id: Option<String>,
+    /// Read over SWD from the interface MCU (Full mode) or from the WB55
+    /// (wireless stack).
+    #[serde(default)]
+    pub device_uid: Option<String>,
+    #[serde(default)]
+    pub flash_size_kb: Option<u16>,
+    #[serde(default)]
+    pub idcode: Option<String>,
+    pub steps: Vec<StepRecord>,
+    pub passed: bool,
+    pub duration_ms: u64,
+}
+
+impl RunRecord {
+    pub fn status(&self) -> &'static str {
+        if self.passed {
+            "PASS"
+        } else {
+            "FAIL"
+        }
+    }
+
+    fn image_sha(&self, label: &str) -> &str {
+        self.images
+            .iter()
+            .find(|i| i.label == label)
+            .map(|i| i.sha256.as_str())
+            .unwrap_or_default()
+    }
+
+    /// True if `filter` (lowercase) appears in the date, profile, mode,
+    /// unique IDs, status or an image name / hash.
+    pub fn matches(&self, filter: &str) -> bool {
+        if filter.is_empty() {
+            return true;
+        }
+
+        let fields = [
+            format_timestamp(self.timestamp),
+            self.profile.to_lowercase(),
+            self.mode.to_lowercase(),
+            self.unique_id.clone().unwrap_or_default().to_lowercase(),
+            self.device_uid.clone().unwrap_or_default().to_lowercase(),
+            self.status().to_lowercase(),
+        ];
+
+        fields.iter().any(|f| f.contains(filter))
+            || self.images.iter().any(|i| {
+                i.file_name.to_lowercase().contains(filter) || i.sha256.starts_with(filter)
+            })
+    }
+}
+
+/// Follows a run as it goes: each step ends when the next one begins.
+#[derive(Debug)]
+pub struct RunRecorder {
+    started: Instant,
+    record: RunRecord,
+    current_step: Option<(String, Instant)>,
+}
+
+impl RunRecorder {
+    pub fn new(profile: &str, mode: &str) -> Self {
+        Self {
+            started: Instant::now(),
+            record: RunRecord {
+                timestamp: now(),
+                profile: profile.to_string(),
+                mode: mode.to_string(),
+                images: Vec::new(),
+                unique_id: None,
+                device_uid: None,
+                flash_size_kb: None,
+                idcode: None,
+                steps: Vec::new(),
+                passed: false,
+                duration_ms: 0,
+            },
+            current_step: None,
+        }
+    }
+
+    fn end_step(&mut self, passed: bool) {
+        if let Some((name, started)) = self.current_step.take() {
+            self.record.steps.push(StepRecord {
+                name,
+                passed,
+                duration_ms: started.elapsed().as_millis() as u64,
+            });
+        }
+    }
+
+    /// The previous step, if any, succeeded since the sequence goes on.
+    pub fn begin_step(&mut self, name: &str) {
+        self.end_step(true);
+        self.current_step = Some((name.to_string(), Instant::now()));
+    }
+
+    pub fn add_image(&mut self, label: &str, file_name: &str, sha256: &str) {
+        self.record.images.push(ImageHash {
+            label: label.to_string(),
+            file_name: file_name.to_string(),
+            sha256: sha256.to_string(),
+        });
+    }
+
+    pub fn set_unique_id(&mut self, unique_id: &str) {
+        self.record.unique_id = Some(unique_id.to_string());
+    }
+
+    pub fn set_device_id(&mut self, id: &DeviceId) {
+        self.record.device_uid = Some(id.uid_hex());
+        self.record.flash_size_kb = Some(id.flash_size_kb);
+        self.record.idcode = Some(format!("0x{:08X}", id.idcode));
+    }
+
+    /// A failed run fails on its last step.
+    pub fn finish(mut self, passed: bool) -> RunRecord {
+        self.end_step(passed);
+        self.record.passed = passed;
+        self.record.duration_ms = self.started.elapsed().as_millis() as u64;
+        self.record
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+pub fn append(record: &RunRecord) -> Result<(), String> {
+    let path = dirs::get_settings_dir()?.join(HISTORY_FILE);
+    let line = serde_json::to_string(record).map_err(|e| e.to_string())?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open the history ({e})"))?;
+
+    writeln!(file, "{line}").map_err(|e| format!("Failed to write the history ({e})"))
+}
+
+/// Every record, oldest first. Unreadable lines (interrupted write) are
+/// skipped.
+pub fn load() -> Result<Vec<RunRecord>, String> {
+    let path = dirs::get_settings_dir()?.join(HISTORY_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read the history ({e})"))?;
+
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// `YYYY-MM-DD HH:MM:SS` in UTC.
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let secs = timestamp % 86400;
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn to_csv(records: &[&RunRecord]) -> String {
+    let mut csv = String::from(
+        "date_utc,profile,mode,unique_id,device_uid,flash_size_kb,idcode,status,duration_s,steps,bootloader_sha256,firmware_sha256,user_program_sha256\n",
+    );
+
+    for record in records {
+        let steps: Vec<String> = record
+            .steps
+            .iter()
+            .map(|s| {
+                format!(
+                    "{} {} {:.1}s",
+                    s.name,
+                    if s.passed { "OK" } else { "FAILED" },
+                    s.duration_ms as f32 / 1000.0
+                )
+            })
+            .collect();
+
+        let fields = [
+            format_timestamp(record.timestamp),
+            record.profile.clone(),
+            record.mode.clone(),
+            record.unique_id.clone().unwrap_or_default(),
+            record.device_uid.clone().unwrap_or_default(),
+            record
+                .flash_size_kb
+                .map(|s| s.to_string())
+                .unwrap_or_default(),
+            record.idcode.clone().unwrap_or_default(),
+            record.status().to_string(),
+            format!("{:.1}", record.duration_ms as f32 / 1000.0),
+            steps.join("; "),
+            record.image_sha("Bootloader").to_string(),
+            record.image_sha("Firmware").to_string(),
+            record.image_sha("User program").to_string(),
+        ];
+
+        let line: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        csv.push_str(&line.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+pub fn export_csv(path: &Path, records: &[&RunRecord]) -> Result<(), String> {
+    fs::write(path, to_csv(records)).map_err(|e| format!("Failed to export the history ({e})"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamp_is_formatted_in_utc() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00");
+        assert_eq!(format_timestamp(1_709_210_096), "2024-02-29 12:34:56");
+    }
+
+    #[test]
+    fn csv_quotes_fields() {
+        let mut recorder = RunRecorder::new("STeaMi, rev B", "Full");
+        recorder.begin_step("Unlock target");
+        recorder.set_unique_id("0778ABCD");
+        let record = recorder.finish(false);
+
+        let csv = to_csv(&[&record]);
+        let line = csv.lines().nth(1).unwrap();
+        assert!(line.contains(",\"STeaMi, rev B\",Full,0778ABCD,,,,FAIL,"));
+        assert!(line.contains("Unlock target FAILED"));
+    }
+}
@@ -6,12 +6,17 @@ use iced::{
 use iced_aw::{TabBar, TabLabel};
 use serde::{Deserialize, Serialize};
 
-use crate::dirs;
+use crate::{dirs, hotplug};
 
-use super::{messages::Message, tab_daplink::TabDaplink, tab_wireless_stack::TabWirelessStack};
+use super::{
+    messages::Message, tab_daplink::TabDaplink, tab_history::TabHistory,
+    tab_option_bytes::TabOptionBytes, tab_wireless_stack::TabWirelessStack,
+};
 
 const DAPLINK_TAB: u16 = 0;
 const WIRELESS_STACK_TAB: u16 = 1;
+const HISTORY_TAB: u16 = 2;
+const OPTION_BYTES_TAB: u16 = 3;
 const SETTINGS_FILE: &str = "fields.json";
 
 #[derive(Default, Debug, Serialize, Deserialize)]
@@ -22,6 +27,10 @@ pub struct MainWindow {
     active_tab: u16,
     tab_daplink: TabDaplink,
     tab_ws: TabWirelessStack,
+    #[serde(skip)]
+    tab_history: TabHistory,
+    #[serde(skip)]
+    tab_option_bytes: TabOptionBytes,
 }
 
 impl MainWindow {
@@ -37,6 +46,8 @@ impl MainWindow {
         match message {
             Message::DapLink(dp_message) => self.tab_daplink.update(dp_message),
             Message::WirelessStack(ws_message) => self.tab_ws.update(ws_message),
+            Message::History(history_message) => self.tab_history.update(history_message),
+            Message::OptionBytes(ob_message) => self.tab_option_bytes.update(ob_message),
 
             Message::ApplicationEvent(event) => match event {
                 Event::Keyboard(_) | Event::Mouse(_) | Event::Touch(_) => Task::none(),
@@ -52,8 +63,9 @@ impl MainWindow {
                             }
                             Err(e) => eprintln!("Failed to get settings dirs (Error: {e}"),
                         };
+                        self.tab_daplink.load_library();
                         self.tab_ws.refresh_serial_ports();
-                        return Task::none();
+                        self.tab_daplink.load_history()
                     }
                     iced::window::Event::CloseRequested => {
                         // Suppress the close while an operation is in progress
@@ -62,7 +74,10 @@ impl MainWindow {
                         // busy state visually; the user can force-quit / end
                         // task the app if they really need out, but a misclick
                         // on the X shouldn't brick the device.
-                        if self.tab_daplink.is_busy() || self.tab_ws.is_busy() {
+                        if self.tab_daplink.is_busy()
+                            || self.tab_ws.is_busy()
+                            || self.tab_option_bytes.is_busy()
+                        {
                             eprintln!(
                                 "Close request ignored: an operation is in progress."
                             );
@@ -87,8 +102,15 @@ impl MainWindow {
                     _ => Task::none(),
                 },
             },
+            Message::DevicesChanged(snapshot) => {
+                self.tab_ws.devices_changed(&snapshot);
+                self.tab_daplink.devices_changed(&snapshot)
+            }
             Message::TabBarSelected(tab_idx) => {
                 self.active_tab = tab_idx;
+                if tab_idx == HISTORY_TAB {
+                    self.tab_history.reload();
+                }
                 Task::none()
             }
         }
@@ -98,12 +120,16 @@ impl MainWindow {
         let mut col = column![TabBar::new(Message::TabBarSelected)
             .push(DAPLINK_TAB, TabLabel::Text("DapLink".into()))
             .push(WIRELESS_STACK_TAB, TabLabel::Text("Wireless Stack".into()))
+            .push(HISTORY_TAB, TabLabel::Text("History".into()))
+            .push(OPTION_BYTES_TAB, TabLabel::Text("Option Bytes".into()))
             .padding(1)
             .set_active_tab(&self.active_tab)];
 
         col = match self.active_tab {
             DAPLINK_TAB => col.push(self.tab_daplink.view()),
             WIRELESS_STACK_TAB => col.push(self.tab_ws.view()),
+            HISTORY_TAB => col.push(self.tab_history.view()),
+            OPTION_BYTES_TAB => col.push(self.tab_option_bytes.view()),
             _ => {
                 eprintln!("Invalid selected tab ({})", self.active_tab);
                 col
@@ -114,7 +140,11 @@ impl MainWindow {
     }
 
     pub fn application_subscription(&self) -> Subscription<Message> {
-        event::listen().map(Message::ApplicationEvent)
+        Subscription::batch([
+            event::listen().map(Message::ApplicationEvent),
+            hotplug::watch().map(Message::DevicesChanged),
+            self.tab_daplink.subscription(),
+        ])
     }
 
     /// Deserialize the on-disk settings, with a fallback to the legacy 0.1.x
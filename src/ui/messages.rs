@@ -3,33 +3,78 @@ use std::path::PathBuf;
 use iced::Event;
 
 use crate::{
+    device_id::{DeviceId, F1Variant, SwdTarget},
+    disk_tool::{CopyOptions, DaplinkCommand, DaplinkDetails},
+    firmware_library::LibraryEntry,
+    hotplug::DeviceSnapshot,
     log_entries::{LogEntries, LogType},
+    option_bytes::{F1Protection, OptionBytes},
+    run_history::RunRecord,
     stackfile_config::WirelessStackFile,
+    usb_ids::KnownProbe,
     ProcessResult,
 };
 
-use super::tab_wireless_stack::{FwStep, SerialPortInfo};
+use super::{
+    tab_daplink::FlashMode,
+    tab_history::StatusFilter,
+    tab_wireless_stack::{FwStep, SerialPortInfo},
+};
 
 pub trait WithLogMessage {
     fn log(log: LogType) -> Self;
 }
 
+pub trait WithProgressMessage {
+    fn progress(done: u64, total: u64) -> Self;
+}
+
 #[derive(Debug, Clone)]
 pub enum Message {
     DapLink(TabDaplinkMessage),
     WirelessStack(TabWsMessage),
+    History(TabHistoryMessage),
+    OptionBytes(TabOptionBytesMessage),
 
     TabBarSelected(u16),
     ApplicationEvent(Event),
+    DevicesChanged(DeviceSnapshot),
+}
+
+#[derive(Debug, Clone)]
+pub enum TabHistoryMessage {
+    FilterChanged(String),
+    StatusFilterSelected(StatusFilter),
+    Refresh,
+    ExportCsv,
+    SelectExportFile(Option<PathBuf>),
+}
+
+#[derive(Debug, Clone)]
+pub enum TabOptionBytesMessage {
+    TargetSelected(SwdTarget),
+    Read,
+    DoneReadDeviceId(Result<DeviceId, String>),
+    DoneRead(Result<OptionBytes, String>),
+    /// Index in `OptionBytes::fields`.
+    FieldToggled(usize, bool),
+    Revert,
+    Write,
+    ConfirmWrite(bool),
+    DoneWrite(Result<ProcessResult, String>),
 }
 
 #[derive(Debug, Clone)]
 pub enum TabDaplinkMessage {
     LogMessage(LogType),
+    HistoryLoaded(Result<Vec<RunRecord>, String>),
 
     BrowseBootloader,
     BrowseFirmware,
     BrowseUserFile,
+    InspectBootloader,
+    InspectFirmware,
+    InspectUserFile,
     SelectBootloader(Option<PathBuf>),
     SelectFirmware(Option<PathBuf>),
     SelectUserFile(Option<PathBuf>),
@@ -38,19 +83,64 @@ pub enum TabDaplinkMessage {
     InputFirmwarePath(String),
     InputUserFilePath(String),
 
+    ProfileSelected(String),
+    ProfileNameChanged(String),
+    SaveProfile,
+    DuplicateProfile,
+    DeleteProfile,
+    ConfirmDeleteProfile(bool),
+    LibraryEntrySelected(LibraryEntry),
+    UseLibraryBootloader,
+    UseLibraryFirmware,
+    LibraryVersionChanged(String),
+    LibrarySourceChanged(String),
+    ImportToLibrary,
+    SelectLibraryImport(Option<PathBuf>),
+    ProbeSelected(KnownProbe),
+    HostFamilySelected(F1Variant),
+
     TimeoutChanged(u64),
     TargetNameChanged(String),
+    FlashModeSelected(FlashMode),
+    CopyOptionsChanged(CopyOptions),
+    RelockToggled(bool),
+    BackupToggled(bool),
+
+    CopyProgress(u64, u64),
+
+    DaplinkCommandSelected(DaplinkCommand),
+    SendDaplinkCommand,
+    DoneDaplinkCommand(Result<DaplinkDetails, String>),
+
+    StartBatch,
+    StopBatch,
+    AcknowledgeFailure,
+    BatchTick,
+    TargetDetected(bool),
 
     StartProcess,
     DoneProcess,
+    DoneReadDeviceId(Result<DeviceId, String>),
+    DoneReadProtection(Result<F1Protection, String>),
+    DoneBackup(PathBuf, Result<ProcessResult, String>),
+    DoneLockProcess(Result<ProcessResult, String>),
     DoneEraseProcess(Result<ProcessResult, String>),
+    DoneBlankCheck(Result<String, String>),
+    DoneVerifyBootloader(Result<String, String>),
     DoneFlashProcess(Result<ProcessResult, String>),
     DoneUnlockProcess(Result<ProcessResult, String>),
 
+    DoneEnterMaintenance(Result<DaplinkDetails, String>),
     DoneWaitMaintenanceDisk(bool),
+    DoneReadMaintenanceDetails(Result<DaplinkDetails, String>),
     DoneCopyFirmware(Result<(), String>),
     DoneWaitingDeviceDisk(bool),
+    DoneReadTargetDetails(Result<DaplinkDetails, String>),
     DoneCopyUserfile(Result<(), String>),
+
+    DoneReadDetailsBeforeBootloader(Result<DaplinkDetails, String>),
+    DoneCopyBootloader(Result<(), String>),
+    DoneReadDetailsAfterBootloader(Result<DaplinkDetails, String>),
 }
 
 #[derive(Debug, Clone)]
@@ -58,8 +148,12 @@ pub enum TabWsMessage {
     StackSelected(WirelessStackFile),
     SerialSelected(SerialPortInfo),
     SerialRefresh,
+    SerialDetected(SerialPortInfo),
 
     StepChange(FwStep),
+    DeviceIdRead(DeviceId),
+    /// Operator status during a FUS or wireless stack upgrade.
+    UpgradeStatus(u32),
     LogMessage(LogType),
     LogMessages(LogEntries),
 }
@@ -75,3 +169,9 @@ impl WithLogMessage for TabDaplinkMessage {
         TabDaplinkMessage::LogMessage(log)
     }
 }
+
+impl WithProgressMessage for TabDaplinkMessage {
+    fn progress(done: u64, total: u64) -> TabDaplinkMessage {
+        TabDaplinkMessage::CopyProgress(done, total)
+    }
+}
@@ -1,5 +1,8 @@
 pub mod log_widget;
 pub mod main_window;
 pub mod messages;
+pub mod progress_widget;
 pub mod tab_daplink;
+pub mod tab_history;
+pub mod tab_option_bytes;
 pub mod tab_wireless_stack;
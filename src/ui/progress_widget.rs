@@ -0,0 +1,32 @@
+use iced::{
+    widget::{column, progress_bar, row, text},
+    Element, Length,
+};
+
+use crate::progress::Progress;
+
+use super::messages::Message;
+
+const TEXT_SIZE: u16 = 12;
+
+/// Step indicator, progress of the current operation and time left.
+pub fn view(progress: &Progress) -> Element<'_, Message> {
+    let mut status = row![].spacing(8).align_y(iced::Alignment::Center);
+    if let Some(fraction) = progress.fraction() {
+        status = status.push(
+            progress_bar(0.0..=1.0, fraction)
+                .height(12)
+                .width(Length::FillPortion(2)),
+        );
+    }
+    status = status.push(
+        text(progress.status())
+            .size(TEXT_SIZE)
+            .width(Length::FillPortion(3)),
+    );
+
+    column![text(progress.title()).size(TEXT_SIZE), status]
+        .spacing(4)
+        .padding([0, 8])
+        .into()
+}
@@ -1,28 +1,123 @@
-use std::{path::PathBuf, str::FromStr, time::Duration};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
+use async_io::Timer;
 use iced::{
     alignment::Horizontal,
-    widget::{button, center, column, container, opaque, row, stack, text, text_input},
-    Element, Length, Task, Theme,
+    futures::{SinkExt, Stream},
+    stream::channel,
+    widget::{
+        button, center, checkbox, column, combo_box, container, horizontal_space, opaque,
+        pick_list, row, stack, text, text_input,
+    },
+    Color, Element, Length, Subscription, Task, Theme,
 };
 use iced_aw::{grid, grid_row, number_input};
 use serde::{Deserialize, Serialize};
 
-use crate::{disk_tool, log_entries::LogType, open_ocd_task, utils};
+use crate::{
+    board_profile::{self, BoardProfile},
+    device_id::{DeviceId, F1Variant, SwdTarget, TargetSettings, ALL_F1_VARIANTS},
+    dirs,
+    disk_tool::{
+        self, CopyOptions, DaplinkCommand, DaplinkDetails, DiskResult, ALL_DAPLINK_COMMANDS,
+        DETAILS_FILENAME,
+    },
+    firmware_image::{DaplinkBuild, FirmwareImage, ImageFormat, ALL_IMAGE_FORMATS},
+    firmware_library::{self, FirmwareLibrary, LibraryEntry},
+    hotplug::DeviceSnapshot,
+    log_entries::LogType,
+    open_ocd_task,
+    option_bytes::RdpLevel,
+    progress::Progress,
+    run_history::{self, RunRecord, RunRecorder},
+    usb_ids::{KnownProbe, KnownUsbDevice, ALL_KNOWN_PROBES},
+    utils,
+};
 
 use super::{
     log_widget::LogWidget,
     messages::{Message, TabDaplinkMessage},
+    progress_widget,
 };
 
 const MAINTENANCE_DISK_NAME: &str = "MAINTENANCE";
 const TIMEOUT_MIN: u64 = 1;
 const TIMEOUT_MAX: u64 = 30;
+const BATCH_TICK: Duration = Duration::from_secs(1);
 
 fn default_target_waiting_time() -> u64 {
     10
 }
 
+/// `42.3 s` or `12 min 05 s`.
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{:.1} s", duration.as_secs_f32())
+    } else {
+        format!("{} min {:02} s", secs / 60, secs % 60)
+    }
+}
+
+fn batch_ticks() -> impl Stream<Item = ()> {
+    channel(1, |mut output| async move {
+        loop {
+            Timer::after(BATCH_TICK).await;
+            if output.send(()).await.is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// What the Start button does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FlashMode {
+    /// Unlock, erase and flash the bootloader over SWD, then the firmware.
+    #[default]
+    Full,
+    /// Reuse the DAPLink bootloader already on the board: only a USB cable
+    /// is needed.
+    FirmwareOnly,
+    /// Let the running interface firmware replace its own bootloader with
+    /// the selected one (needs automation allowed).
+    BootloaderUpdate,
+}
+
+const ALL_FLASH_MODES: [FlashMode; 3] = [
+    FlashMode::Full,
+    FlashMode::FirmwareOnly,
+    FlashMode::BootloaderUpdate,
+];
+
+/// Where a production batch is at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BatchPhase {
+    /// Waiting for a board to be connected.
+    WaitBoard,
+    Running(Instant),
+    /// The last board passed, waiting for it to be removed.
+    WaitRemoval,
+    /// The last board failed: nothing starts until the operator
+    /// acknowledges it.
+    Failed,
+}
+
+#[derive(Debug)]
+struct Batch {
+    phase: BatchPhase,
+    passed: u32,
+    failed: u32,
+    started: Instant,
+    /// Result and duration of the last board.
+    last: Option<(bool, Duration)>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TabDaplink {
     #[serde(skip)]
@@ -33,8 +128,84 @@ pub struct TabDaplink {
     #[serde(default = "default_target_waiting_time")]
     target_waiting_time: u64,
     target_name: String,
+    /// Probe the interface MCU is programmed with.
+    #[serde(default)]
+    probe: KnownProbe,
+    /// Interface MCU the board is expected to carry.
+    #[serde(default)]
+    host_family: F1Variant,
+    #[serde(default = "board_profile::default_profiles")]
+    profiles: Vec<BoardProfile>,
+    /// Profile the fields were last loaded from or saved to.
+    #[serde(default)]
+    selected_profile: Option<String>,
+    /// Name used by the Save button.
+    #[serde(skip)]
+    profile_name: String,
+    #[serde(skip)]
+    library: FirmwareLibrary,
+    #[serde(skip)]
+    library_selection: Option<LibraryEntry>,
+    #[serde(skip)]
+    library_version: String,
+    #[serde(skip)]
+    library_source: String,
+    #[serde(default)]
+    flash_mode: FlashMode,
+    #[serde(default)]
+    copy_options: CopyOptions,
+    /// Set read-out protection level 1 once the bootloader is flashed.
+    #[serde(default)]
+    relock: bool,
+    /// Keep a copy of the interface MCU flash in the library before erasing it.
+    #[serde(default)]
+    backup_before_erase: bool,
+    /// Target mount names that showed up during past runs.
+    #[serde(default)]
+    seen_target_names: Vec<String>,
+    #[serde(skip)]
+    target_name_options: combo_box::State<String>,
     #[serde(skip)]
     log_widget: LogWidget,
+    #[serde(skip)]
+    connected_probe: Option<KnownProbe>,
+    #[serde(skip)]
+    connected_disks: Vec<DiskResult>,
+    /// A board running DAPLink is plugged on the USB bus.
+    #[serde(skip)]
+    daplink_connected: bool,
+    /// The last SWD detection found an interface MCU behind the probe.
+    #[serde(skip)]
+    swd_target_present: bool,
+    #[serde(skip)]
+    is_detecting: bool,
+    #[serde(skip)]
+    daplink_command: DaplinkCommand,
+    /// Bootloader version reported before a bootloader update.
+    #[serde(skip)]
+    previous_bootloader_version: Option<String>,
+    /// The target still has to be unlocked once the backup is done.
+    #[serde(skip)]
+    unlock_after_backup: bool,
+    /// Step indicator of the run in progress.
+    #[serde(skip)]
+    progress: Option<Progress>,
+    /// Set when a run reaches its end without error.
+    #[serde(skip)]
+    run_passed: bool,
+    #[serde(skip)]
+    batch: Option<Batch>,
+    /// Record of the run in progress, appended to the history at the end.
+    #[serde(skip)]
+    run: Option<RunRecorder>,
+    /// Passed runs of the history, for the time left of the next ones.
+    #[serde(skip)]
+    passed_runs: Vec<RunRecord>,
+    /// OpenOCD settings for the chip detected by the last ID read.
+    #[serde(skip)]
+    target_settings: TargetSettings,
+    #[serde(skip)]
+    device_id: Option<DeviceId>,
 }
 
 impl TabDaplink {
@@ -46,9 +217,153 @@ impl TabDaplink {
         self.is_readonly
     }
 
+    /// Reads the firmware library index, a broken one is reported in the log
+    /// and leaves the library empty.
+    pub fn load_library(&mut self) {
+        match FirmwareLibrary::load() {
+            Ok(library) => self.library = library,
+            Err(e) => self.log_widget.push(LogType::Warning(e)),
+        }
+    }
+
+    /// Reads the run history on its own thread, it only grows.
+    pub fn load_history(&self) -> Task<Message> {
+        Task::perform(utils::unblock(run_history::load), |x| {
+            Message::DapLink(TabDaplinkMessage::HistoryLoaded(x))
+        })
+    }
+
+    /// Live update from the hot-plug watcher, feeds the connection indicators
+    /// and moves the production batch on.
+    pub fn devices_changed(&mut self, snapshot: &DeviceSnapshot) -> Task<Message> {
+        // The probe of the profile first, any other one is only shown
+        let probes: Vec<KnownProbe> = snapshot
+            .usb_devices
+            .iter()
+            .filter_map(KnownProbe::identify)
+            .collect();
+        self.connected_probe = probes
+            .iter()
+            .find(|p| **p == self.probe)
+            .or(probes.first())
+            .copied();
+        self.connected_disks = snapshot.disks.clone();
+        self.daplink_connected = snapshot.usb_devices.iter().any(|d| {
+            matches!(
+                KnownUsbDevice::identify(d.vid, d.pid, d.product.as_deref()),
+                Some(KnownUsbDevice::DapLink | KnownUsbDevice::Steami)
+            )
+        });
+        self.refresh_target_name_options();
+        self.advance_batch()
+    }
+
+    /// Ticks once a second while a batch is active, to refresh the session
+    /// time and look for a board on the SWD bus.
+    pub fn subscription(&self) -> Subscription<Message> {
+        match self.batch {
+            Some(_) => Subscription::run(batch_ticks)
+                .map(|_| Message::DapLink(TabDaplinkMessage::BatchTick)),
+            None => Subscription::none(),
+        }
+    }
+
+    /// Starts the next board once it is connected, and waits for the
+    /// previous one to be removed. Nothing starts while an SWD detection
+    /// still holds the probe.
+    fn advance_batch(&mut self) -> Task<Message> {
+        let is_present = self.is_board_present() && !self.is_detecting;
+        let batch = match self.batch.as_mut() {
+            Some(batch) => batch,
+            None => return Task::none(),
+        };
+
+        match batch.phase {
+            BatchPhase::WaitBoard if is_present => {
+                batch.phase = BatchPhase::Running(Instant::now());
+                self.log_widget.push(LogType::InfoNoPrefix("\n\n".into()));
+                self.log_widget.push(LogType::Info(format!(
+                    "Board #{} connected",
+                    batch.passed + batch.failed + 1
+                )));
+                Task::done(Message::DapLink(TabDaplinkMessage::StartProcess))
+            }
+            BatchPhase::WaitRemoval if !is_present => {
+                batch.phase = BatchPhase::WaitBoard;
+                Task::none()
+            }
+            _ => Task::none(),
+        }
+    }
+
+    /// What "a board is connected" means for the batch: for a full flash, a
+    /// DAPLink USB device or an interface MCU answering on the SWD bus (a
+    /// blank board has nothing else), the DAPLink drive for the USB only
+    /// modes.
+    fn is_board_present(&self) -> bool {
+        let is_mounted =
+            |name: &str| !name.is_empty() && self.connected_disks.iter().any(|d| d.matches(name));
+
+        match self.flash_mode {
+            FlashMode::Full => self.daplink_connected || self.swd_target_present,
+            FlashMode::FirmwareOnly => {
+                is_mounted(MAINTENANCE_DISK_NAME) || is_mounted(&self.target_name)
+            }
+            FlashMode::BootloaderUpdate => is_mounted(&self.target_name),
+        }
+    }
+
+    /// Next batch phase once the board is done with (or the failure
+    /// acknowledged): a board still connected must be removed first.
+    fn next_batch_phase(&self) -> BatchPhase {
+        if self.is_board_present() {
+            BatchPhase::WaitRemoval
+        } else {
+            BatchPhase::WaitBoard
+        }
+    }
+
+    /// Known DAPLink names, names seen in past runs and the removable drives
+    /// currently mounted.
+    fn refresh_target_name_options(&mut self) {
+        let mut options: Vec<String> = disk_tool::KNOWN_DAPLINK_NAMES
+            .iter()
+            .filter(|name| **name != MAINTENANCE_DISK_NAME)
+            .map(|name| name.to_string())
+            .chain(self.seen_target_names.iter().cloned())
+            .chain(
+                self.connected_disks
+                    .iter()
+                    .filter(|d| d.is_removable && d.name != MAINTENANCE_DISK_NAME)
+                    .map(|d| d.name.clone()),
+            )
+            .collect();
+        options.sort();
+        options.dedup();
+
+        if options != self.target_name_options.options() {
+            self.target_name_options = combo_box::State::new(options);
+        }
+    }
+
     pub fn update(&mut self, message: TabDaplinkMessage) -> Task<Message> {
         match message {
-            TabDaplinkMessage::LogMessage(log) => self.log_widget.push(log),
+            TabDaplinkMessage::LogMessage(log) => {
+                if let (Some(progress), LogType::Info(line)) = (self.progress.as_mut(), &log) {
+                    progress.feed_openocd(line);
+                }
+                self.log_widget.push(log);
+            }
+            TabDaplinkMessage::HistoryLoaded(result) => match result {
+                // Runs that ended while loading are newer, they stay last.
+                Ok(records) => {
+                    let mut runs: Vec<RunRecord> =
+                        records.into_iter().filter(|r| r.passed).collect();
+                    runs.append(&mut self.passed_runs);
+                    self.passed_runs = runs;
+                }
+                Err(e) => self.log_widget.push(LogType::Warning(e)),
+            },
             TabDaplinkMessage::BrowseBootloader => {
                 self.is_readonly = true;
 
@@ -56,7 +371,7 @@ impl TabDaplink {
                     utils::select_file(
                         self.bootloader_path.clone(),
                         "Select Bootloader file",
-                        false,
+                        &ALL_IMAGE_FORMATS,
                     ),
                     |x| Message::DapLink(TabDaplinkMessage::SelectBootloader(x)),
                 );
@@ -65,7 +380,11 @@ impl TabDaplink {
                 self.is_readonly = true;
 
                 return Task::perform(
-                    utils::select_file(self.firmware_path.clone(), "Select Firmware file", false),
+                    utils::select_file(
+                        self.firmware_path.clone(),
+                        "Select Firmware file",
+                        &ALL_IMAGE_FORMATS,
+                    ),
                     |x| Message::DapLink(TabDaplinkMessage::SelectFirmware(x)),
                 );
             }
@@ -75,12 +394,22 @@ impl TabDaplink {
                     utils::select_file(
                         self.user_file_path.clone(),
                         "Select user program file",
-                        true,
+                        &[ImageFormat::Binary, ImageFormat::IntelHex],
                     ),
                     |x| Message::DapLink(TabDaplinkMessage::SelectUserFile(x)),
                 );
             }
 
+            TabDaplinkMessage::InspectBootloader => {
+                self.inspect_image("Bootloader", self.bootloader_path.clone())
+            }
+            TabDaplinkMessage::InspectFirmware => {
+                self.inspect_image("Firmware", self.firmware_path.clone())
+            }
+            TabDaplinkMessage::InspectUserFile => {
+                self.inspect_image("User program", self.user_file_path.clone())
+            }
+
             TabDaplinkMessage::SelectBootloader(p) => {
                 match p {
                     Some(p) => self.bootloader_path = p,
@@ -121,10 +450,222 @@ impl TabDaplink {
             }
 
             TabDaplinkMessage::TargetNameChanged(s) => self.target_name = s,
+            TabDaplinkMessage::LibraryEntrySelected(entry) => self.library_selection = Some(entry),
+            TabDaplinkMessage::UseLibraryBootloader => {
+                if let Some(path) = self.library_selection_path() {
+                    self.bootloader_path = path;
+                }
+            }
+            TabDaplinkMessage::UseLibraryFirmware => {
+                if let Some(path) = self.library_selection_path() {
+                    self.firmware_path = path;
+                }
+            }
+            TabDaplinkMessage::LibraryVersionChanged(s) => self.library_version = s,
+            TabDaplinkMessage::LibrarySourceChanged(s) => self.library_source = s,
+            TabDaplinkMessage::ImportToLibrary => {
+                self.is_readonly = true;
+                return Task::perform(
+                    utils::select_file(
+                        self.firmware_path.clone(),
+                        "Import in the firmware library",
+                        &ALL_IMAGE_FORMATS,
+                    ),
+                    |x| Message::DapLink(TabDaplinkMessage::SelectLibraryImport(x)),
+                );
+            }
+            TabDaplinkMessage::SelectLibraryImport(p) => {
+                self.is_readonly = false;
+                if let Some(p) = p {
+                    let board = self.selected_profile.clone().unwrap_or_default();
+                    match self.library.import(
+                        &p,
+                        &self.library_version,
+                        &self.library_source,
+                        &board,
+                    ) {
+                        Ok(entry) => {
+                            self.log_widget.push(LogType::Info(format!(
+                                "Imported '{}' in the library (SHA-256 {})",
+                                entry.file_name, entry.sha256
+                            )));
+                            self.library_selection = Some(entry);
+                            self.library_version.clear();
+                            self.library_source.clear();
+                        }
+                        Err(e) => self.log_widget.push(LogType::Error(e)),
+                    }
+                }
+            }
+
+            TabDaplinkMessage::ProbeSelected(probe) => self.probe = probe,
+            TabDaplinkMessage::HostFamilySelected(family) => self.host_family = family,
+
+            TabDaplinkMessage::ProfileSelected(name) => {
+                if let Some(profile) = self.profiles.iter().find(|p| p.name == name).cloned() {
+                    self.apply_profile(&profile);
+                    self.log_widget
+                        .push(LogType::Info(format!("Profile '{name}' loaded")));
+                }
+            }
+            TabDaplinkMessage::ProfileNameChanged(name) => self.profile_name = name,
+            TabDaplinkMessage::SaveProfile => self.save_profile(),
+            TabDaplinkMessage::DuplicateProfile => {
+                let source = match self.selected() {
+                    Some(profile) => profile.clone(),
+                    None => self.current_profile(self.profile_name.trim()),
+                };
+                let name = board_profile::unique_name(&self.profiles, &source.name);
+                let profile = BoardProfile {
+                    name: name.clone(),
+                    ..source
+                };
+
+                self.profiles.push(profile.clone());
+                self.apply_profile(&profile);
+                self.log_widget
+                    .push(LogType::Info(format!("Profile '{name}' created")));
+            }
+            TabDaplinkMessage::DeleteProfile => {
+                if let Some(name) = self.selected_profile.clone() {
+                    self.is_readonly = true;
+                    return Task::perform(
+                        utils::confirm(
+                            "Delete profile".into(),
+                            format!("Delete the '{name}' profile?"),
+                        ),
+                        |x| Message::DapLink(TabDaplinkMessage::ConfirmDeleteProfile(x)),
+                    );
+                }
+            }
+            TabDaplinkMessage::ConfirmDeleteProfile(confirmed) => {
+                self.is_readonly = false;
+                if let (true, Some(name)) = (confirmed, self.selected_profile.take()) {
+                    self.profiles.retain(|p| p.name != name);
+                    self.profile_name.clear();
+                    self.log_widget
+                        .push(LogType::Info(format!("Profile '{name}' deleted")));
+                }
+            }
+            TabDaplinkMessage::FlashModeSelected(mode) => self.flash_mode = mode,
+            TabDaplinkMessage::CopyOptionsChanged(options) => self.copy_options = options,
+            TabDaplinkMessage::RelockToggled(relock) => self.relock = relock,
+            TabDaplinkMessage::BackupToggled(backup) => self.backup_before_erase = backup,
+
+            TabDaplinkMessage::DaplinkCommandSelected(command) => self.daplink_command = command,
+            TabDaplinkMessage::SendDaplinkCommand => return self.send_daplink_command(),
+            TabDaplinkMessage::DoneDaplinkCommand(result) => {
+                match result {
+                    Ok(details) => {
+                        for line in details.summary() {
+                            self.log_widget.push(LogType::Info(format!("    {line}")));
+                        }
+                        self.log_widget.push(LogType::Info(format!(
+                            "{} applied",
+                            self.daplink_command.filename()
+                        )));
+                    }
+                    Err(e) => self.log_widget.push(LogType::Error(e)),
+                }
+                self.is_readonly = false;
+            }
+
+            TabDaplinkMessage::StartBatch => {
+                if self.target_name.is_empty() {
+                    self.log_widget.push(LogType::Error(
+                        "The batch mode needs a target mount name to verify each board.".into(),
+                    ));
+                    return Task::none();
+                }
+
+                self.log_widget.push(LogType::InfoNoPrefix("\n\n".into()));
+                self.log_widget.push(LogType::Info(format!(
+                    "Batch started ({}), connect the first board",
+                    self.flash_mode
+                )));
+                self.batch = Some(Batch {
+                    phase: BatchPhase::WaitBoard,
+                    passed: 0,
+                    failed: 0,
+                    started: Instant::now(),
+                    last: None,
+                });
+                // A board may already be there.
+                return self.advance_batch();
+            }
+            TabDaplinkMessage::StopBatch => {
+                if let Some(batch) = self.batch.take() {
+                    self.log_widget.push(LogType::Info(format!(
+                        "Batch stopped: {} passed, {} failed in {}",
+                        batch.passed,
+                        batch.failed,
+                        format_duration(batch.started.elapsed())
+                    )));
+                }
+            }
+            TabDaplinkMessage::BatchTick => {
+                let is_waiting = matches!(
+                    self.batch.as_ref().map(|b| b.phase),
+                    Some(BatchPhase::WaitBoard | BatchPhase::WaitRemoval)
+                );
+                if self.flash_mode == FlashMode::Full
+                    && is_waiting
+                    && !self.is_detecting
+                    && self.connected_probe.is_some()
+                {
+                    self.is_detecting = true;
+                    return Task::perform(open_ocd_task::detect_target(self.probe), |x| {
+                        Message::DapLink(TabDaplinkMessage::TargetDetected(x))
+                    });
+                }
+            }
+            TabDaplinkMessage::TargetDetected(present) => {
+                self.is_detecting = false;
+                self.swd_target_present = present;
+                return self.advance_batch();
+            }
+            TabDaplinkMessage::AcknowledgeFailure => {
+                let phase = self.next_batch_phase();
+                if let Some(batch) = self.batch.as_mut() {
+                    batch.phase = phase;
+                }
+            }
 
             TabDaplinkMessage::StartProcess => {
+                self.run_passed = false;
+                let mut run = RunRecorder::new(
+                    self.selected_profile.as_deref().unwrap_or_default(),
+                    &self.flash_mode.to_string(),
+                );
+                run.begin_step("Check fields");
+                self.run = Some(run);
+                let mut progress = Progress::new(None, self.reference_steps());
+                progress.begin_step("Check fields");
+                self.progress = Some(progress);
+
                 if !self.validate_fields() {
-                    return Task::none();
+                    return Task::done(Message::DapLink(TabDaplinkMessage::DoneProcess));
+                }
+                self.log_image_hashes();
+
+                match self.flash_mode {
+                    FlashMode::Full => (),
+                    FlashMode::FirmwareOnly => {
+                        self.is_readonly = true;
+                        return self.enter_maintenance();
+                    }
+                    FlashMode::BootloaderUpdate => {
+                        self.is_readonly = true;
+                        self.begin_step(format!("Read {DETAILS_FILENAME} on {}", self.target_name));
+                        return Task::perform(
+                            disk_tool::read_details(self.target_name.clone()),
+                            |x| {
+                                Message::DapLink(
+                                    TabDaplinkMessage::DoneReadDetailsBeforeBootloader(x),
+                                )
+                            },
+                        );
+                    }
                 }
 
                 match open_ocd_task::is_installed() {
@@ -132,27 +673,174 @@ impl TabDaplink {
                         if !is_install {
                             self.log_widget
                                 .push(LogType::Error("OpenOCD is not found".into()));
-                            return Task::none();
+                            return Task::done(Message::DapLink(TabDaplinkMessage::DoneProcess));
                         }
                     }
                     Err(e) => {
                         self.log_widget.push(LogType::Error(format!(
                             "Failed to test openocd installation: {e}"
                         )));
-                        return Task::none();
+                        return Task::done(Message::DapLink(TabDaplinkMessage::DoneProcess));
                     }
                 };
 
-                self.log_widget.push(LogType::InfoNoPrefix("\n\n".into()));
-                self.log_widget.push(LogType::Info("Unlock target".into()));
+                self.begin_step("Read device ID".into());
                 self.is_readonly = true;
-                return Task::perform(open_ocd_task::unlock_target(), |x| {
-                    Message::DapLink(TabDaplinkMessage::DoneUnlockProcess(x))
+                self.target_settings = self.profile_settings();
+                self.device_id = None;
+                return Task::perform(
+                    open_ocd_task::read_device_id(SwdTarget::Stm32F1, self.target_settings),
+                    |x| Message::DapLink(TabDaplinkMessage::DoneReadDeviceId(x)),
+                );
+            }
+
+            TabDaplinkMessage::DoneReadDeviceId(result) => {
+                // A locked chip can't be read: the unlock comes next, so this
+                // is only a warning.
+                match result {
+                    Ok(id) => {
+                        for line in id.summary() {
+                            self.log_widget.push(LogType::Info(line));
+                        }
+                        if let Some(run) = self.run.as_mut() {
+                            run.set_device_id(&id);
+                        }
+
+                        let variant = F1Variant::detect(&id);
+                        self.target_settings = self.profile_settings_for(variant, Some(&id));
+                        self.device_id = Some(id);
+                        let log = format!(
+                            "Detected {variant}, OpenOCD settings: {}",
+                            self.target_settings.openocd_args().join(" ")
+                        );
+                        self.log_widget.push(match variant {
+                            F1Variant::Unknown => LogType::Warning(log),
+                            _ => LogType::Info(log),
+                        });
+                        if variant != self.host_family {
+                            self.log_widget.push(LogType::Warning(format!(
+                                "The profile expects a {}.",
+                                self.host_family
+                            )));
+                        }
+                    }
+                    Err(e) => {
+                        self.log_widget.push(LogType::Warning(e));
+                        self.log_widget.push(LogType::Info(format!(
+                            "Using the {} settings of the profile: {}",
+                            self.host_family,
+                            self.target_settings.openocd_args().join(" ")
+                        )));
+                    }
+                }
+
+                self.begin_step("Check read protection".into());
+                return Task::perform(open_ocd_task::read_protection(self.target_settings), |x| {
+                    Message::DapLink(TabDaplinkMessage::DoneReadProtection(x))
                 });
             }
 
+            TabDaplinkMessage::DoneReadProtection(result) => {
+                match result {
+                    // Unlocking needs a power cycle: skip it when there is
+                    // nothing to remove.
+                    Ok(protection)
+                        if protection.rdp == RdpLevel::Level0
+                            && !protection.is_write_protected() =>
+                    {
+                        self.log_widget.push(LogType::Info(
+                            "Read protection level 0, no write protection, no unlock needed".into(),
+                        ));
+                        if self.backup_before_erase {
+                            return self.backup_task();
+                        }
+                        return self.erase_task();
+                    }
+                    Ok(protection) if protection.rdp == RdpLevel::Level0 => {
+                        self.log_widget.push(LogType::Info(format!(
+                            "Read protection level 0, write protected pages (FLASH_WRPR 0x{:08X})",
+                            protection.wrpr
+                        )));
+                        if self.backup_before_erase {
+                            self.unlock_after_backup = true;
+                            return self.backup_task();
+                        }
+                    }
+                    Ok(protection) => {
+                        self.log_widget
+                            .push(LogType::Info(format!("Read protection {}", protection.rdp)));
+                        if self.backup_before_erase {
+                            self.log_widget.push(LogType::Warning(
+                                "The flash is read protected: no backup possible".into(),
+                            ));
+                        }
+                    }
+                    Err(e) => {
+                        self.log_widget.push(LogType::Warning(e));
+                        if self.backup_before_erase {
+                            self.log_widget.push(LogType::Warning(
+                                "Protection unknown: backup skipped".into(),
+                            ));
+                        }
+                    }
+                }
+
+                return self.unlock_task();
+            }
+
             TabDaplinkMessage::DoneProcess => {
                 self.is_readonly = false;
+                self.progress = None;
+
+                let passed = self.run_passed;
+                if let Some(run) = self.run.take() {
+                    let record = run.finish(passed);
+                    if let Err(e) = run_history::append(&record) {
+                        self.log_widget.push(LogType::Warning(e));
+                    }
+                    if passed {
+                        self.passed_runs.push(record);
+                    }
+                }
+                let phase = if passed {
+                    self.next_batch_phase()
+                } else {
+                    BatchPhase::Failed
+                };
+                if let Some(batch) = self.batch.as_mut() {
+                    if let BatchPhase::Running(started) = batch.phase {
+                        let elapsed = started.elapsed();
+                        if passed {
+                            batch.passed += 1;
+                        } else {
+                            batch.failed += 1;
+                        }
+                        batch.last = Some((passed, elapsed));
+                        batch.phase = phase;
+
+                        let board = batch.passed + batch.failed;
+                        self.log_widget.push(if passed {
+                            LogType::Info(format!(
+                                "Board #{board} PASS in {}",
+                                format_duration(elapsed)
+                            ))
+                        } else {
+                            LogType::Error(format!(
+                                "Board #{board} FAIL in {}",
+                                format_duration(elapsed)
+                            ))
+                        });
+                    }
+                }
+            }
+
+            TabDaplinkMessage::CopyProgress(done, total) => {
+                if let Some(progress) = self.progress.as_mut() {
+                    progress.set_fraction(
+                        done as f32 / total.max(1) as f32,
+                        format!("Copy {} / {} KB", done / 1024, total.max(1).div_ceil(1024)),
+                    );
+                }
             }
 
             TabDaplinkMessage::DoneUnlockProcess(result) => {
@@ -169,11 +857,7 @@ impl TabDaplink {
                     match result.code {
                         Some(code) => {
                             if code == 0 {
-                                self.log_widget.push(LogType::InfoNoPrefix("\n\n".into()));
-                                self.log_widget.push(LogType::Info("Erase target".into()));
-                                return Task::perform(open_ocd_task::erase_target(), |x| {
-                                    Message::DapLink(TabDaplinkMessage::DoneEraseProcess(x))
-                                });
+                                return self.erase_task();
                             } else {
                                 self.log_widget
                                     .push(LogType::Warning(format!("Exit code: {}", code)));
@@ -200,13 +884,13 @@ impl TabDaplink {
                     match result.code {
                         Some(code) => {
                             if code == 0 {
-                                self.log_widget.push(LogType::InfoNoPrefix("\n\n".into()));
-                                self.log_widget
-                                    .push(LogType::Info("Flash bootloader".into()));
-
+                                self.begin_step("Blank check".into());
                                 return Task::perform(
-                                    open_ocd_task::flash_target(self.bootloader_path.clone()),
-                                    |x| Message::DapLink(TabDaplinkMessage::DoneFlashProcess(x)),
+                                    open_ocd_task::blank_check(
+                                        self.target_settings,
+                                        self.flash_size_kb(),
+                                    ),
+                                    |x| Message::DapLink(TabDaplinkMessage::DoneBlankCheck(x)),
                                 );
                             } else {
                                 self.log_widget
@@ -220,10 +904,77 @@ impl TabDaplink {
                 }
                 return Task::done(Message::DapLink(TabDaplinkMessage::DoneProcess));
             }
+            TabDaplinkMessage::DoneBackup(path, result) => {
+                match result {
+                    Ok(result) if result.code == Some(0) && path.is_file() => {
+                        self.save_backup(&path)
+                    }
+                    Ok(result) => {
+                        self.log_widget.from_log_entries(&result.log);
+                        self.log_widget.push(LogType::Warning(
+                            "The flash is not readable: no backup".into(),
+                        ));
+                    }
+                    Err(e) => self.log_widget.push(LogType::Warning(format!(
+                        "Failed to run backup process. Error: {e}"
+                    ))),
+                }
+                let _ = fs::remove_file(&path);
+
+                return self.after_backup_task();
+            }
+            TabDaplinkMessage::DoneLockProcess(result) => {
+                match result {
+                    Ok(result) => {
+                        self.log_widget.from_log_entries(&result.log);
+                        match result.code {
+                            Some(0) => {
+                                self.log_widget.push(LogType::Warning(
+                                    "Read protection level 1 set: unplug the board to apply it."
+                                        .into(),
+                                ));
+                                self.run_passed = true;
+                            }
+                            Some(code) => self
+                                .log_widget
+                                .push(LogType::Warning(format!("Exit code: {}", code))),
+                            None => self
+                                .log_widget
+                                .push(LogType::Warning("Process terminated by signal.".into())),
+                        }
+                    }
+                    Err(e) => self.log_widget.push(LogType::Error(format!(
+                        "Failed to run lock process. Error: {e}"
+                    ))),
+                }
+                return Task::done(Message::DapLink(TabDaplinkMessage::DoneProcess));
+            }
+            TabDaplinkMessage::DoneBlankCheck(result) => match result {
+                Ok(summary) => {
+                    self.log_widget.push(LogType::Info(summary));
+                    self.begin_step("Flash bootloader".into());
+                    return self.flash_task();
+                }
+                Err(e) => {
+                    self.log_widget.push(LogType::Error(e));
+                    return Task::done(Message::DapLink(TabDaplinkMessage::DoneProcess));
+                }
+            },
+            TabDaplinkMessage::DoneVerifyBootloader(result) => match result {
+                Ok(summary) => {
+                    self.log_widget.push(LogType::Info(summary));
+                    return self.wait_maintenance_task();
+                }
+                Err(e) => {
+                    self.log_widget
+                        .push(LogType::Error(format!("Bootloader verify failed ({e})")));
+                    return Task::done(Message::DapLink(TabDaplinkMessage::DoneProcess));
+                }
+            },
             TabDaplinkMessage::DoneFlashProcess(result) => {
                 if result.is_err() {
                     self.log_widget.push(LogType::Error(format!(
-                        "Failed to run erase process. Error: {}",
+                        "Failed to run flash and read-back process. Error: {}",
                         result.err().unwrap()
                     )));
                 } else {
@@ -232,28 +983,15 @@ impl TabDaplink {
                     self.log_widget.from_log_entries(&result.log);
 
                     match result.code {
-                        Some(code) => {
-                            if code == 0 {
-                                self.log_widget.push(LogType::InfoNoPrefix("\n\n".into()));
-                                self.log_widget.push(LogType::Info(format!(
-                                    "Wait for '{MAINTENANCE_DISK_NAME}' drive"
-                                )));
-                                return Task::perform(
-                                    disk_tool::wait_for_drive(
-                                        MAINTENANCE_DISK_NAME.into(),
-                                        Duration::from_secs(self.target_waiting_time),
-                                    ),
-                                    |x| {
-                                        Message::DapLink(
-                                            TabDaplinkMessage::DoneWaitMaintenanceDisk(x),
-                                        )
-                                    },
-                                );
-                            } else {
-                                self.log_widget
-                                    .push(LogType::Warning(format!("Exit code: {}", code)));
-                            }
+                        Some(0) => {
+                            self.begin_step("Verify bootloader".into());
+                            return Task::perform(
+                                open_ocd_task::verify_image(self.bootloader_path.clone()),
+                                |x| Message::DapLink(TabDaplinkMessage::DoneVerifyBootloader(x)),
+                            );
                         }
+                        // The exit code was already streamed with the output
+                        Some(_) => {}
                         None => self
                             .log_widget
                             .push(LogType::Warning("Process terminated by signal.".into())),
@@ -262,6 +1000,18 @@ impl TabDaplink {
                 return Task::done(Message::DapLink(TabDaplinkMessage::DoneProcess));
             }
 
+            TabDaplinkMessage::DoneEnterMaintenance(result) => match result {
+                Ok(details) => {
+                    return Task::done(Message::DapLink(
+                        TabDaplinkMessage::DoneReadMaintenanceDetails(Ok(details)),
+                    ))
+                }
+                Err(e) => {
+                    self.log_widget.push(LogType::Warning(e));
+                    return self.wait_maintenance_with_reset();
+                }
+            },
+
             TabDaplinkMessage::DoneWaitMaintenanceDisk(is_found) => {
                 if !is_found {
                     self.log_widget.push(LogType::Error(format!(
@@ -270,27 +1020,41 @@ impl TabDaplink {
                     return Task::done(Message::DapLink(TabDaplinkMessage::DoneProcess));
                 }
 
-                self.log_widget.push(LogType::InfoNoPrefix("\n\n".into()));
-                self.log_widget.push(LogType::Info(format!(
-                    "Copy firmware to {MAINTENANCE_DISK_NAME}"
-                )));
-                return Task::perform(
-                    disk_tool::copy_file_to_disk(
-                        MAINTENANCE_DISK_NAME.into(),
-                        self.firmware_path.clone(),
-                    ),
-                    |x| Message::DapLink(TabDaplinkMessage::DoneCopyFirmware(x)),
+                self.begin_step(format!(
+                    "Read {DETAILS_FILENAME} on {MAINTENANCE_DISK_NAME}"
+                ));
+                return Task::perform(disk_tool::read_details(MAINTENANCE_DISK_NAME.into()), |x| {
+                    Message::DapLink(TabDaplinkMessage::DoneReadMaintenanceDetails(x))
+                });
+            }
+            TabDaplinkMessage::DoneReadMaintenanceDetails(result) => {
+                // In firmware only mode the bootloader already on the board
+                // is kept, there is no file to compare it with.
+                let bootloader = match self.flash_mode {
+                    FlashMode::Full => Some(self.bootloader_path.clone()),
+                    FlashMode::FirmwareOnly | FlashMode::BootloaderUpdate => None,
+                };
+                if self
+                    .check_details(result, "Bootloader", bootloader)
+                    .is_none()
+                {
+                    return Task::done(Message::DapLink(TabDaplinkMessage::DoneProcess));
+                }
+
+                self.begin_step(format!("Copy firmware to {MAINTENANCE_DISK_NAME}"));
+                return self.copy_task(
+                    MAINTENANCE_DISK_NAME.into(),
+                    self.firmware_path.clone(),
+                    (!self.target_name.is_empty()).then(|| self.target_name.clone()),
+                    self.copy_options,
+                    TabDaplinkMessage::DoneCopyFirmware,
                 );
             }
             TabDaplinkMessage::DoneCopyFirmware(result) => {
                 match result {
                     Ok(_) => {
-                        if self.user_file_path.exists() && self.user_file_path.is_file() {
-                            self.log_widget.push(LogType::InfoNoPrefix("\n\n".into()));
-                            self.log_widget.push(LogType::Info(format!(
-                                "Wait for '{}' drive",
-                                self.target_name
-                            )));
+                        if !self.target_name.is_empty() {
+                            self.begin_step(format!("Wait for '{}' drive", self.target_name));
                             return Task::perform(
                                 disk_tool::wait_for_drive(
                                     self.target_name.clone(),
@@ -299,8 +1063,11 @@ impl TabDaplink {
                                 |x| Message::DapLink(TabDaplinkMessage::DoneWaitingDeviceDisk(x)),
                             );
                         } else {
-                            self.log_widget
-                                .push(LogType::Warning("No user file. Skip.".into()));
+                            self.log_widget.push(LogType::Warning(
+                                "No target mount name, the installed firmware is not verified."
+                                    .into(),
+                            ));
+                            return self.passed_task();
                         }
                     }
                     Err(e) => self
@@ -319,30 +1086,125 @@ impl TabDaplink {
                     return Task::done(Message::DapLink(TabDaplinkMessage::DoneProcess));
                 }
 
-                self.log_widget.push(LogType::InfoNoPrefix("\n\n".into()));
-                self.log_widget.push(LogType::Info(format!(
-                    "Copy firmware to {}",
-                    self.target_name
-                )));
-                return Task::perform(
-                    disk_tool::copy_file_to_disk(
-                        self.target_name.clone(),
-                        self.user_file_path.clone(),
-                    ),
-                    |x| Message::DapLink(TabDaplinkMessage::DoneCopyUserfile(x)),
-                );
+                if !self.seen_target_names.contains(&self.target_name) {
+                    self.seen_target_names.push(self.target_name.clone());
+                    self.refresh_target_name_options();
+                }
+
+                self.begin_step(format!("Read {DETAILS_FILENAME} on {}", self.target_name));
+                return Task::perform(disk_tool::read_details(self.target_name.clone()), |x| {
+                    Message::DapLink(TabDaplinkMessage::DoneReadTargetDetails(x))
+                });
             }
 
-            TabDaplinkMessage::DoneCopyUserfile(result) => {
-                match result {
-                    Ok(_) => (),
-                    Err(e) => self
-                        .log_widget
-                        .push(LogType::Error(format!("Copy failed ({e})"))),
+            TabDaplinkMessage::DoneReadTargetDetails(result) => {
+                let firmware = Some(self.firmware_path.clone());
+                if self.check_details(result, "Interface", firmware).is_none() {
+                    return Task::done(Message::DapLink(TabDaplinkMessage::DoneProcess));
+                }
+
+                if !(self.user_file_path.exists() && self.user_file_path.is_file()) {
+                    self.log_widget
+                        .push(LogType::Warning("No user file. Skip.".into()));
+                    return self.passed_task();
+                }
+
+                self.begin_step(format!("Copy user program to {}", self.target_name));
+                return self.copy_task(
+                    self.target_name.clone(),
+                    self.user_file_path.clone(),
+                    None,
+                    self.copy_options,
+                    TabDaplinkMessage::DoneCopyUserfile,
+                );
+            }
+
+            TabDaplinkMessage::DoneReadDetailsBeforeBootloader(result) => {
+                let details = match self.check_details(result, "Interface", None) {
+                    Some(details) => details,
+                    None => return Task::done(Message::DapLink(TabDaplinkMessage::DoneProcess)),
+                };
+
+                if details.get("Automation allowed") != Some("1") {
+                    self.log_widget.push(LogType::Error(format!(
+                        "Automation is not allowed on '{}', the interface will refuse the bootloader. Send '{}' while holding the reset button first.",
+                        self.target_name,
+                        DaplinkCommand::AutomationOn
+                    )));
+                    return Task::done(Message::DapLink(TabDaplinkMessage::DoneProcess));
+                }
+
+                self.previous_bootloader_version =
+                    details.bootloader_version().map(|v| v.to_string());
+
+                self.begin_step(format!("Copy bootloader to {}", self.target_name));
+                return self.copy_task(
+                    self.target_name.clone(),
+                    self.bootloader_path.clone(),
+                    None,
+                    self.copy_options,
+                    TabDaplinkMessage::DoneCopyBootloader,
+                );
+            }
+            TabDaplinkMessage::DoneCopyBootloader(result) => {
+                if let Err(e) = result {
+                    self.log_widget
+                        .push(LogType::Error(format!("Copy failed ({e})")));
+                    return Task::done(Message::DapLink(TabDaplinkMessage::DoneProcess));
+                }
+
+                self.begin_step(format!("Read {DETAILS_FILENAME} on {}", self.target_name));
+                return Task::perform(disk_tool::read_details(self.target_name.clone()), |x| {
+                    Message::DapLink(TabDaplinkMessage::DoneReadDetailsAfterBootloader(x))
+                });
+            }
+            TabDaplinkMessage::DoneReadDetailsAfterBootloader(result) => {
+                if let Some(details) = self.check_details(result, "Interface", None) {
+                    let previous = self.previous_bootloader_version.take();
+                    let expected = FirmwareImage::load(&self.bootloader_path)
+                        .map(|image| image.daplink_version());
+                    match (details.bootloader_version(), expected) {
+                        (_, Err(e)) => self.log_widget.push(LogType::Error(e)),
+                        (_, Ok(None)) => self.log_widget.push(LogType::Error(
+                            "The selected bootloader carries no DAPLink version.".into(),
+                        )),
+                        (None, _) => self.log_widget.push(LogType::Error(format!(
+                            "No bootloader version in {DETAILS_FILENAME}"
+                        ))),
+                        (Some(version), Ok(Some(expected))) if version != expected => {
+                            self.log_widget.push(LogType::Error(format!(
+                                "Bootloader version {version} installed, {expected} expected from the selected file."
+                            )))
+                        }
+                        (Some(version), _) if previous.as_deref() == Some(version) => {
+                            self.log_widget.push(LogType::Warning(format!(
+                                "Bootloader version unchanged ({version}): the board already had it."
+                            )));
+                            self.run_passed = true;
+                        }
+                        (Some(version), _) => {
+                            self.log_widget.push(LogType::Info(format!(
+                                "Bootloader updated: {} -> {version}",
+                                previous.as_deref().unwrap_or("unknown")
+                            )));
+                            self.run_passed = true;
+                        }
+                    }
                 }
                 self.log_widget.push(LogType::InfoNoPrefix("\n\n".into()));
                 return Task::done(Message::DapLink(TabDaplinkMessage::DoneProcess));
             }
+
+            TabDaplinkMessage::DoneCopyUserfile(result) => {
+                if let Err(e) = result {
+                    self.log_widget
+                        .push(LogType::Error(format!("Copy failed ({e})")));
+                    self.log_widget.push(LogType::InfoNoPrefix("\n\n".into()));
+                    return Task::done(Message::DapLink(TabDaplinkMessage::DoneProcess));
+                }
+                self.log_widget.push(LogType::InfoNoPrefix("\n\n".into()));
+                return self.passed_task();
+            }
         }
 
         Task::none()
@@ -359,7 +1221,9 @@ impl TabDaplink {
                     )
                     .on_input(|s| Message::DapLink(TabDaplinkMessage::InputBootloaderPath(s)))
                     .width(Length::Fill),
-                    button("...").on_press(Message::DapLink(TabDaplinkMessage::BrowseBootloader))
+                    button("...").on_press(Message::DapLink(TabDaplinkMessage::BrowseBootloader)),
+                    button(text("🔍").shaping(text::Shaping::Advanced))
+                        .on_press(Message::DapLink(TabDaplinkMessage::InspectBootloader))
                 ]
                 .spacing(8)
             ),
@@ -369,7 +1233,9 @@ impl TabDaplink {
                     text_input("Firmware", self.firmware_path.to_str().unwrap_or_default())
                         .on_input(|s| Message::DapLink(TabDaplinkMessage::InputFirmwarePath(s)))
                         .width(Length::Fill),
-                    button("...").on_press(Message::DapLink(TabDaplinkMessage::BrowseFirmware))
+                    button("...").on_press(Message::DapLink(TabDaplinkMessage::BrowseFirmware)),
+                    button(text("🔍").shaping(text::Shaping::Advanced))
+                        .on_press(Message::DapLink(TabDaplinkMessage::InspectFirmware))
                 ]
                 .spacing(8)
             ),
@@ -382,7 +1248,9 @@ impl TabDaplink {
                     )
                     .on_input(|s| Message::DapLink(TabDaplinkMessage::InputUserFilePath(s)))
                     .width(Length::Fill),
-                    button("...").on_press(Message::DapLink(TabDaplinkMessage::BrowseUserFile))
+                    button("...").on_press(Message::DapLink(TabDaplinkMessage::BrowseUserFile)),
+                    button(text("🔍").shaping(text::Shaping::Advanced))
+                        .on_press(Message::DapLink(TabDaplinkMessage::InspectUserFile))
                 ]
                 .spacing(8)
             ),
@@ -393,12 +1261,131 @@ impl TabDaplink {
         .column_widths(&[Length::Shrink, Length::Fill])
         .padding(8);
 
+        let has_selection = self.library_selection.is_some();
+        let library_control = column![
+            row![
+                text("Library"),
+                pick_list(
+                    self.library.entries(),
+                    self.library_selection.clone(),
+                    |x| Message::DapLink(TabDaplinkMessage::LibraryEntrySelected(x))
+                )
+                .placeholder("Imported images")
+                .width(Length::Fill),
+                button("Bootloader").on_press_maybe(
+                    has_selection
+                        .then_some(Message::DapLink(TabDaplinkMessage::UseLibraryBootloader))
+                ),
+                button("Firmware").on_press_maybe(
+                    has_selection
+                        .then_some(Message::DapLink(TabDaplinkMessage::UseLibraryFirmware))
+                ),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            row![
+                text_input("Version", &self.library_version)
+                    .on_input(|s| Message::DapLink(TabDaplinkMessage::LibraryVersionChanged(s)))
+                    .width(Length::FillPortion(1)),
+                text_input("Source URL", &self.library_source)
+                    .on_input(|s| Message::DapLink(TabDaplinkMessage::LibrarySourceChanged(s)))
+                    .width(Length::FillPortion(3)),
+                button("Import...").on_press(Message::DapLink(TabDaplinkMessage::ImportToLibrary)),
+            ]
+            .spacing(8),
+        ]
+        .spacing(8)
+        .padding([0, 8]);
+
+        let profile_names: Vec<String> = self.profiles.iter().map(|p| p.name.clone()).collect();
+        let profile_control = column![
+            row![
+                text("Profile"),
+                pick_list(profile_names, self.selected_profile.clone(), |x| {
+                    Message::DapLink(TabDaplinkMessage::ProfileSelected(x))
+                })
+                .placeholder("Board profile")
+                .width(Length::Fill),
+                button("Duplicate").on_press(Message::DapLink(TabDaplinkMessage::DuplicateProfile)),
+                button("Delete").style(button::danger).on_press_maybe(
+                    self.selected_profile
+                        .as_ref()
+                        .map(|_| Message::DapLink(TabDaplinkMessage::DeleteProfile))
+                ),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            row![
+                text_input("Profile name", &self.profile_name)
+                    .on_input(|s| Message::DapLink(TabDaplinkMessage::ProfileNameChanged(s)))
+                    .width(Length::Fill),
+                button("Save").on_press(Message::DapLink(TabDaplinkMessage::SaveProfile)),
+            ]
+            .spacing(8),
+        ]
+        .spacing(8)
+        .padding([0, 8]);
+
         let grid_settings = grid!(
+            grid_row!(
+                "Mode",
+                pick_list(&ALL_FLASH_MODES[..], Some(&self.flash_mode), |x| {
+                    Message::DapLink(TabDaplinkMessage::FlashModeSelected(x))
+                })
+                .width(Length::Fill)
+            ),
+            grid_row!(
+                "Probe / interface MCU",
+                row![
+                    pick_list(&ALL_KNOWN_PROBES[..], Some(&self.probe), |x| {
+                        Message::DapLink(TabDaplinkMessage::ProbeSelected(x))
+                    })
+                    .width(Length::Fill),
+                    pick_list(&ALL_F1_VARIANTS[..], Some(&self.host_family), |x| {
+                        Message::DapLink(TabDaplinkMessage::HostFamilySelected(x))
+                    })
+                    .width(Length::Fill),
+                ]
+                .spacing(8)
+            ),
             grid_row!(
                 "Target mount name",
-                text_input("STeaMi, DIS_L4IOT, ...", &self.target_name)
-                    .on_input(|s| Message::DapLink(TabDaplinkMessage::TargetNameChanged(s)))
-                    .width(200),
+                combo_box(
+                    &self.target_name_options,
+                    "STeaMi, DIS_L4IOT, ...",
+                    Some(&self.target_name),
+                    |s| Message::DapLink(TabDaplinkMessage::TargetNameChanged(s))
+                )
+                .on_input(|s| Message::DapLink(TabDaplinkMessage::TargetNameChanged(s)))
+                .width(200),
+            ),
+            grid_row!(
+                "Copy",
+                row![
+                    checkbox("Sync", self.copy_options.sync).on_toggle(|sync| {
+                        Message::DapLink(TabDaplinkMessage::CopyOptionsChanged(CopyOptions {
+                            sync,
+                            ..self.copy_options
+                        }))
+                    }),
+                    checkbox("Unmount", self.copy_options.unmount).on_toggle(|unmount| {
+                        Message::DapLink(TabDaplinkMessage::CopyOptionsChanged(CopyOptions {
+                            unmount,
+                            ..self.copy_options
+                        }))
+                    }),
+                ]
+                .spacing(16)
+            ),
+            grid_row!(
+                "Backup",
+                checkbox("Back up flash before erase", self.backup_before_erase)
+                    .on_toggle(|x| Message::DapLink(TabDaplinkMessage::BackupToggled(x))),
+            ),
+            grid_row!(
+                "Protection",
+                checkbox("Set RDP level 1 after flashing", self.relock)
+                    .on_toggle(|x| Message::DapLink(TabDaplinkMessage::RelockToggled(x))),
             ),
             grid_row!(
                 "Timeout (s) for mount points",
@@ -415,6 +1402,41 @@ impl TabDaplink {
         .column_widths(&[Length::Shrink, Length::Fill])
         .padding(8);
 
+        let connected_color = Color::from_rgb8(0, 0x88, 0);
+        let probe_status = match self.connected_probe {
+            Some(probe) => text(format!("● Probe: {probe}")).color(connected_color),
+            None => text("○ Probe: not connected"),
+        };
+        let is_disk_connected =
+            |name: &str| !name.is_empty() && self.connected_disks.iter().any(|d| d.matches(name));
+        let target_status = if is_disk_connected(&self.target_name) {
+            text(format!("● Target: {}", self.target_name)).color(connected_color)
+        } else if is_disk_connected(MAINTENANCE_DISK_NAME) {
+            text(format!("● Target: {MAINTENANCE_DISK_NAME}")).color(connected_color)
+        } else {
+            text("○ Target: not connected")
+        };
+        let status_row = row![
+            probe_status.size(12).shaping(text::Shaping::Advanced),
+            target_status.size(12).shaping(text::Shaping::Advanced)
+        ]
+        .spacing(16)
+        .padding([0, 8]);
+
+        let daplink_control = row![
+            text("DAPLink drive"),
+            pick_list(
+                &ALL_DAPLINK_COMMANDS[..],
+                Some(&self.daplink_command),
+                |x| { Message::DapLink(TabDaplinkMessage::DaplinkCommandSelected(x)) }
+            )
+            .width(Length::Fill),
+            button("Send").on_press(Message::DapLink(TabDaplinkMessage::SendDaplinkCommand))
+        ]
+        .spacing(8)
+        .padding([0, 8])
+        .align_y(iced::Alignment::Center);
+
         let start_button = button(
             text("Start 🚀")
                 .shaping(text::Shaping::Advanced)
@@ -422,7 +1444,18 @@ impl TabDaplink {
                 .align_x(Horizontal::Center),
         )
         .width(Length::Fill)
-        .on_press(Message::DapLink(TabDaplinkMessage::StartProcess));
+        .on_press_maybe(
+            self.batch
+                .is_none()
+                .then_some(Message::DapLink(TabDaplinkMessage::StartProcess)),
+        );
+
+        let batch_view = self.view_batch();
+
+        let progress_view: Element<Message> = match &self.progress {
+            Some(progress) => progress_widget::view(progress),
+            None => row![].into(),
+        };
 
         let log_view = container(self.log_widget.view())
             .height(Length::Fill)
@@ -431,7 +1464,16 @@ impl TabDaplink {
         let final_view = if self.is_readonly {
             column![
                 stack![
-                    column![grid_files, grid_settings, start_button].spacing(16),
+                    column![
+                        profile_control,
+                        grid_files,
+                        library_control,
+                        grid_settings,
+                        status_row,
+                        daplink_control,
+                        start_button
+                    ]
+                    .spacing(16),
                     opaque(center(text("")).style(|theme: &Theme| {
                         let mut bg = theme.palette().background;
                         bg.a = 0.8;
@@ -441,23 +1483,49 @@ impl TabDaplink {
                         }
                     }))
                 ],
+                batch_view,
+                progress_view,
                 log_view
             ]
         } else {
-            column![grid_files, grid_settings, start_button, log_view]
+            column![
+                profile_control,
+                grid_files,
+                library_control,
+                grid_settings,
+                status_row,
+                daplink_control,
+                start_button,
+                batch_view,
+                progress_view,
+                log_view
+            ]
         };
 
         final_view.spacing(16).padding(8).into()
     }
 
     fn validate_fields(&mut self) -> bool {
-        if !self.bootloader_path.exists() {
+        let needs_bootloader = self.flash_mode != FlashMode::FirmwareOnly;
+        if needs_bootloader && !self.bootloader_path.exists() {
             self.log_widget.push(LogType::Error(
                 "Invalide bootloader file (no such file or directory)".to_owned(),
             ));
             return false;
         }
 
+        if needs_bootloader {
+            self.check_image_build(
+                "bootloader",
+                &self.bootloader_path.clone(),
+                DaplinkBuild::Bootloader,
+            );
+        }
+
+        if self.flash_mode == FlashMode::BootloaderUpdate {
+            return self.validate_target_name();
+        }
+
         if !self.firmware_path.exists() {
             self.log_widget.push(LogType::Error(
                 "Invalide firmware file (no such file or directory)".to_owned(),
@@ -465,6 +1533,21 @@ impl TabDaplink {
             return false;
         }
 
+        if self.flash_mode == FlashMode::Full && self.connected_probe != Some(self.probe) {
+            self.log_widget.push(LogType::Warning(format!(
+                "No {} is connected, the profile uses it for the full flash.",
+                self.probe
+            )));
+        }
+
+        if let Some(image) = self.check_image_build(
+            "firmware",
+            &self.firmware_path.clone(),
+            DaplinkBuild::Interface,
+        ) {
+            self.check_firmware_board(&image);
+        }
+
         let user_path_set = self.user_file_path.as_os_str().len() > 0;
         if user_path_set && !self.user_file_path.exists() {
             self.log_widget.push(LogType::Warning(
@@ -472,6 +1555,658 @@ impl TabDaplink {
             ));
         }
 
+        if (user_path_set || !self.target_name.is_empty()) && !self.validate_target_name() {
+            return false;
+        }
+
+        true
+    }
+
+    fn view_batch(&self) -> Element<'_, Message> {
+        let batch = match self.batch.as_ref() {
+            Some(batch) => batch,
+            None => {
+                return row![
+                    horizontal_space(),
+                    button("Start batch").on_press(Message::DapLink(TabDaplinkMessage::StartBatch))
+                ]
+                .padding([0, 8])
+                .into()
+            }
+        };
+
+        let pass_color = Color::from_rgb8(0, 0x88, 0);
+        let fail_color = Color::from_rgb8(0xAA, 0, 0);
+        let (status, color) = match (batch.phase, batch.last) {
+            (BatchPhase::Running(_), _) => ("RUNNING".to_string(), None),
+            (BatchPhase::Failed, _) => ("FAIL".to_string(), Some(fail_color)),
+            (_, Some((true, _))) => ("PASS".to_string(), Some(pass_color)),
+            (_, Some((false, _))) => ("FAIL".to_string(), Some(fail_color)),
+            (_, None) => ("READY".to_string(), None),
+        };
+        let mut status = text(status).size(48);
+        if let Some(color) = color {
+            status = status.color(color);
+        }
+
+        let instruction = match batch.phase {
+            BatchPhase::WaitBoard => "Connect the next board",
+            BatchPhase::Running(_) => "Flashing, do not disconnect",
+            BatchPhase::WaitRemoval => "Remove the board",
+            BatchPhase::Failed => "Put the board aside, then acknowledge",
+        };
+
+        let last = match batch.last {
+            Some((_, elapsed)) => format!("Last board: {}", format_duration(elapsed)),
+            None => String::new(),
+        };
+
+        let mut buttons = row![].spacing(8);
+        if batch.phase == BatchPhase::Failed {
+            buttons = buttons.push(
+                button("Acknowledge")
+                    .style(button::danger)
+                    .on_press(Message::DapLink(TabDaplinkMessage::AcknowledgeFailure)),
+            );
+        }
+        buttons = buttons.push(
+            button("Stop batch").on_press_maybe(
+                (!matches!(batch.phase, BatchPhase::Running(_)))
+                    .then_some(Message::DapLink(TabDaplinkMessage::StopBatch)),
+            ),
+        );
+
+        row![
+            status,
+            column![
+                text(instruction),
+                text(format!(
+                    "Passed: {}   Failed: {}   Session: {}",
+                    batch.passed,
+                    batch.failed,
+                    format_duration(batch.started.elapsed())
+                ))
+                .size(12),
+                text(last).size(12),
+            ]
+            .spacing(4)
+            .width(Length::Fill),
+            buttons
+        ]
+        .spacing(16)
+        .padding([0, 8])
+        .align_y(iced::Alignment::Center)
+        .into()
+    }
+
+    /// End of a successful sequence. In full mode the read protection, when
+    /// asked, is set last: it only applies after a power-on reset, so nothing
+    /// can be waited for once it is set.
+    fn passed_task(&mut self) -> Task<Message> {
+        if self.flash_mode == FlashMode::Full && self.relock {
+            self.begin_step("Set read protection level 1".into());
+            return Task::perform(open_ocd_task::lock_target(self.target_settings), |x| {
+                Message::DapLink(TabDaplinkMessage::DoneLockProcess(x))
+            });
+        }
+
+        self.run_passed = true;
+        Task::done(Message::DapLink(TabDaplinkMessage::DoneProcess))
+    }
+
+    /// Flash size of the interface MCU, F103xB when it could not be read.
+    fn flash_size_kb(&self) -> u16 {
+        self.device_id
+            .as_ref()
+            .map(|id| id.flash_size_kb)
+            .unwrap_or(128)
+    }
+
+    fn backup_task(&mut self) -> Task<Message> {
+        self.begin_step("Back up flash".into());
+
+        let uid = self
+            .device_id
+            .as_ref()
+            .map(|id| id.uid_hex())
+            .unwrap_or("unknown".into());
+        let date = run_history::format_timestamp(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        )
+        .replace(['-', ':'], "")
+        .replace(' ', "-");
+        let path = match dirs::get_tmp_dir() {
+            Ok(tmp) => tmp.join(format!("backup-{uid}-{date}.bin")),
+            Err(e) => {
+                self.log_widget.push(LogType::Warning(e));
+                return self.after_backup_task();
+            }
+        };
+        Task::perform(
+            open_ocd_task::backup_flash(self.target_settings, self.flash_size_kb(), path.clone()),
+            move |x| Message::DapLink(TabDaplinkMessage::DoneBackup(path.clone(), x)),
+        )
+    }
+
+    /// Imports the dump in the library, unless the chip was blank.
+    fn save_backup(&mut self, path: &Path) {
+        match fs::read(path) {
+            Ok(content) if content.iter().all(|b| *b == 0xFF) => {
+                self.log_widget.push(LogType::Info(
+                    "The flash is blank: nothing to back up".into(),
+                ));
+                return;
+            }
+            Ok(_) => (),
+            Err(e) => {
+                self.log_widget
+                    .push(LogType::Warning(format!("Failed to read the backup ({e})")));
+                return;
+            }
+        }
+
+        let source = match &self.device_id {
+            Some(id) => format!("Backup of {} ({})", id.uid_hex(), id.device_name()),
+            None => "Backup".to_string(),
+        };
+        let board = self.selected_profile.clone().unwrap_or_default();
+        match self.library.import(path, "", &source, &board) {
+            Ok(entry) => self.log_widget.push(LogType::Info(format!(
+                "The flash was readable, backup saved in the library as '{}' (SHA-256 {})",
+                entry.file_name, entry.sha256
+            ))),
+            Err(e) => self.log_widget.push(LogType::Warning(e)),
+        }
+    }
+
+    /// Write protected pages are only removed by the unlock, which erases
+    /// the flash as well.
+    fn after_backup_task(&mut self) -> Task<Message> {
+        if std::mem::take(&mut self.unlock_after_backup) {
+            self.unlock_task()
+        } else {
+            self.erase_task()
+        }
+    }
+
+    fn unlock_task(&mut self) -> Task<Message> {
+        self.begin_step("Unlock target".into());
+        Task::perform(open_ocd_task::unlock_target(self.target_settings), |x| {
+            Message::DapLink(TabDaplinkMessage::DoneUnlockProcess(x))
+        })
+    }
+
+    fn erase_task(&mut self) -> Task<Message> {
+        self.begin_step("Erase target".into());
+        Task::perform(open_ocd_task::erase_target(self.target_settings), |x| {
+            Message::DapLink(TabDaplinkMessage::DoneEraseProcess(x))
+        })
+    }
+
+    /// The freshly flashed bootloader shows up as the maintenance drive.
+    fn wait_maintenance_task(&mut self) -> Task<Message> {
+        self.begin_step(format!("Wait for '{MAINTENANCE_DISK_NAME}' drive"));
+        Task::perform(
+            disk_tool::wait_for_drive(
+                MAINTENANCE_DISK_NAME.into(),
+                Duration::from_secs(self.target_waiting_time),
+            ),
+            |x| Message::DapLink(TabDaplinkMessage::DoneWaitMaintenanceDisk(x)),
+        )
+    }
+
+    /// OpenOCD output is streamed so the programming milestones show up live.
+    fn flash_task(&self) -> Task<Message> {
+        let bin_path = self.bootloader_path.clone();
+        let settings = self.target_settings;
+
+        Task::run(
+            channel(16, move |mut o| async move {
+                let result = open_ocd_task::flash_target(bin_path, settings, &mut o).await;
+                let _ = o.send(TabDaplinkMessage::DoneFlashProcess(result)).await;
+            }),
+            Message::DapLink,
+        )
+    }
+
+    /// Logs the start of a step of the sequence and records it in the run.
+    fn begin_step(&mut self, title: String) {
+        self.log_widget.push(LogType::InfoNoPrefix("\n\n".into()));
+        if let Some(run) = self.run.as_mut() {
+            run.begin_step(&title);
+        }
+        if let Some(progress) = self.progress.as_mut() {
+            progress.begin_step(&title);
+        }
+        self.log_widget.push(LogType::Info(title));
+    }
+
+    /// Step durations of the last successful run with the same profile and
+    /// mode, to estimate the time left.
+    fn reference_steps(&self) -> Vec<u64> {
+        let profile = self.selected_profile.as_deref().unwrap_or_default();
+        let mode = self.flash_mode.to_string();
+
+        self.passed_runs
+            .iter()
+            .rev()
+            .find(|r| r.profile == profile && r.mode == mode)
+            .map(|r| r.steps.iter().map(|s| s.duration_ms).collect())
+            .unwrap_or_default()
+    }
+
+    fn selected(&self) -> Option<&BoardProfile> {
+        let name = self.selected_profile.as_ref()?;
+        self.profiles.iter().find(|p| &p.name == name)
+    }
+
+    /// OpenOCD settings of the profile's probe, for the detected variant.
+    fn profile_settings_for(&self, variant: F1Variant, id: Option<&DeviceId>) -> TargetSettings {
+        TargetSettings {
+            probe: self.probe,
+            ..variant.settings(id)
+        }
+    }
+
+    /// Settings used until the chip is identified.
+    fn profile_settings(&self) -> TargetSettings {
+        self.profile_settings_for(self.host_family, None)
+    }
+
+    fn current_profile(&self, name: &str) -> BoardProfile {
+        BoardProfile {
+            name: name.to_string(),
+            bootloader_path: self.bootloader_path.clone(),
+            firmware_path: self.firmware_path.clone(),
+            user_file_path: self.user_file_path.clone(),
+            target_name: self.target_name.clone(),
+            target_waiting_time: self.target_waiting_time,
+            probe: self.probe,
+            host_family: self.host_family,
+        }
+    }
+
+    fn apply_profile(&mut self, profile: &BoardProfile) {
+        self.bootloader_path = profile.bootloader_path.clone();
+        self.firmware_path = profile.firmware_path.clone();
+        self.user_file_path = profile.user_file_path.clone();
+        self.target_name = profile.target_name.clone();
+        self.target_waiting_time = profile.target_waiting_time.clamp(TIMEOUT_MIN, TIMEOUT_MAX);
+        self.probe = profile.probe;
+        self.host_family = profile.host_family;
+        self.selected_profile = Some(profile.name.clone());
+        self.profile_name = profile.name.clone();
+    }
+
+    /// Stores the fields under the typed name, replacing the profile with
+    /// the same name if any.
+    fn save_profile(&mut self) {
+        let name = self.profile_name.trim().to_string();
+        if name.is_empty() {
+            self.log_widget
+                .push(LogType::Error("The profile needs a name.".into()));
+            return;
+        }
+
+        let profile = self.current_profile(&name);
+        match self.profiles.iter_mut().find(|p| p.name == name) {
+            Some(existing) => *existing = profile,
+            None => self.profiles.push(profile),
+        }
+        self.selected_profile = Some(name.clone());
+        self.log_widget
+            .push(LogType::Info(format!("Profile '{name}' saved")));
+    }
+
+    fn library_selection_path(&mut self) -> Option<PathBuf> {
+        match self.library_selection.as_ref()?.checked_path() {
+            Ok(path) => Some(path),
+            Err(e) => {
+                self.log_widget.push(LogType::Error(e));
+                None
+            }
+        }
+    }
+
+    /// Records which exact files this run uses: their SHA-256 and the
+    /// library entry they match, if any.
+    fn log_image_hashes(&mut self) {
+        let mut images = Vec::new();
+        if self.flash_mode != FlashMode::FirmwareOnly {
+            images.push(("Bootloader", self.bootloader_path.clone()));
+        }
+        if self.flash_mode != FlashMode::BootloaderUpdate {
+            images.push(("Firmware", self.firmware_path.clone()));
+            images.push(("User program", self.user_file_path.clone()));
+        }
+
+        self.log_widget.push(LogType::Info("Images".into()));
+        for (label, path) in images {
+            if !path.is_file() {
+                continue;
+            }
+
+            match firmware_library::sha256_file(&path) {
+                Ok(sha256) => {
+                    if let Some(run) = self.run.as_mut() {
+                        run.add_image(
+                            label,
+                            path.file_name()
+                                .and_then(|f| f.to_str())
+                                .unwrap_or_default(),
+                            &sha256,
+                        );
+                    }
+                    let origin = match self.library.find(&sha256) {
+                        Some(entry) => format!("library: {entry}"),
+                        None => "not in the library".to_string(),
+                    };
+                    self.log_widget.push(LogType::Info(format!(
+                        "    {label}: {} SHA-256 {sha256} ({origin})",
+                        path.file_name()
+                            .and_then(|f| f.to_str())
+                            .unwrap_or("undefined")
+                    )));
+                }
+                Err(e) => self.log_widget.push(LogType::Warning(e)),
+            }
+        }
+    }
+
+    /// Logs what the inspector finds in `path`.
+    fn inspect_image(&mut self, label: &str, path: PathBuf) {
+        self.log_widget.push(LogType::InfoNoPrefix("\n\n".into()));
+        self.log_widget.push(LogType::Info(format!(
+            "{label}: {}",
+            path.to_str().unwrap_or("undefined")
+        )));
+
+        match FirmwareImage::load(&path) {
+            Ok(image) => {
+                for line in image.summary() {
+                    self.log_widget.push(LogType::Info(format!("    {line}")));
+                }
+            }
+            Err(e) => self.log_widget.push(LogType::Error(e)),
+        }
+    }
+
+    /// Warns when the file selected as `label` is not the `expected` DAPLink
+    /// program (bootloader and interface firmware swapped, or not DAPLink).
+    fn check_image_build(
+        &mut self,
+        label: &str,
+        path: &Path,
+        expected: DaplinkBuild,
+    ) -> Option<FirmwareImage> {
+        let image = match FirmwareImage::load(path) {
+            Ok(image) => image,
+            Err(e) => {
+                self.log_widget.push(LogType::Warning(e));
+                return None;
+            }
+        };
+
+        match image.daplink_build() {
+            Some(build) if build == expected => (),
+            Some(build) => self.log_widget.push(LogType::Warning(format!(
+                "The {label} file is a DAPLink {build}, not a {expected}. Are the files swapped?"
+            ))),
+            None => self.log_widget.push(LogType::Warning(format!(
+                "The {label} file does not look like a DAPLink {expected}."
+            ))),
+        }
+
+        Some(image)
+    }
+
+    /// Warns when the firmware is built for another board than the one
+    /// behind the target drive, e.g. the L475 firmware on a WB55 board.
+    fn check_firmware_board(&mut self, image: &FirmwareImage) {
+        let expected = match image.daplink_board_id() {
+            Some(id) => id,
+            None => return,
+        };
+
+        let details = self
+            .connected_disks
+            .iter()
+            .find(|d| d.matches(&self.target_name))
+            .and_then(|d| fs::read_to_string(d.path.join(DETAILS_FILENAME)).ok())
+            .map(|content| DaplinkDetails::parse(&content));
+
+        if let Some(board_id) = details.as_ref().and_then(DaplinkDetails::board_id) {
+            if !board_id.eq_ignore_ascii_case(&expected) {
+                self.log_widget.push(LogType::Warning(format!(
+                    "The firmware is built for board ID {expected}, the board on '{}' reports {board_id}. Is it the right firmware?",
+                    self.target_name
+                )));
+            }
+        }
+    }
+
+    /// Copies `file` on the drive without blocking the UI, the progress is
+    /// reported with `CopyProgress` before `done` is sent.
+    fn copy_task(
+        &mut self,
+        disk_name: String,
+        file: PathBuf,
+        next_drive: Option<String>,
+        options: CopyOptions,
+        done: fn(Result<(), String>) -> TabDaplinkMessage,
+    ) -> Task<Message> {
+        let timeout = Duration::from_secs(self.target_waiting_time);
+
+        Task::run(
+            channel(16, move |mut o| async move {
+                let result = disk_tool::copy_file_to_disk(
+                    disk_name,
+                    file,
+                    next_drive,
+                    timeout,
+                    options,
+                    Some(&mut o),
+                )
+                .await;
+                let _ = o.send(done(result)).await;
+            }),
+            Message::DapLink,
+        )
+    }
+
+    /// Firmware only mode: gets the board in MAINTENANCE. Nothing to do if it
+    /// already is, otherwise `START_BL.ACT` is sent to the target drive.
+    fn enter_maintenance(&mut self) -> Task<Message> {
+        self.begin_step(format!("Enter '{MAINTENANCE_DISK_NAME}' mode"));
+
+        let is_mounted =
+            |name: &str| !name.is_empty() && self.connected_disks.iter().any(|d| d.matches(name));
+
+        if is_mounted(MAINTENANCE_DISK_NAME) {
+            return Task::done(Message::DapLink(
+                TabDaplinkMessage::DoneWaitMaintenanceDisk(true),
+            ));
+        }
+
+        if !is_mounted(&self.target_name) {
+            return self.wait_maintenance_with_reset();
+        }
+
+        self.log_widget.push(LogType::Info(format!(
+            "Send {} to {}",
+            DaplinkCommand::StartBootloader.filename(),
+            self.target_name
+        )));
+        Task::perform(
+            disk_tool::send_daplink_command(
+                self.target_name.clone(),
+                MAINTENANCE_DISK_NAME.into(),
+                DaplinkCommand::StartBootloader,
+                Duration::from_secs(self.target_waiting_time),
+            ),
+            |x| Message::DapLink(TabDaplinkMessage::DoneEnterMaintenance(x)),
+        )
+    }
+
+    /// Fallback when DAPLink cannot be switched by software (automation
+    /// disabled, or the board is not plugged yet).
+    fn wait_maintenance_with_reset(&mut self) -> Task<Message> {
+        self.log_widget.push(LogType::Warning(format!(
+            "Hold the reset button while plugging the board to enter '{MAINTENANCE_DISK_NAME}'"
+        )));
+        self.log_widget.push(LogType::Info(format!(
+            "Wait for '{MAINTENANCE_DISK_NAME}' drive"
+        )));
+        Task::perform(
+            disk_tool::wait_for_drive(
+                MAINTENANCE_DISK_NAME.into(),
+                Duration::from_secs(self.target_waiting_time),
+            ),
+            |x| Message::DapLink(TabDaplinkMessage::DoneWaitMaintenanceDisk(x)),
+        )
+    }
+
+    /// Sends the selected magic file to whichever DAPLink drive is mounted,
+    /// the target drive or MAINTENANCE.
+    fn send_daplink_command(&mut self) -> Task<Message> {
+        let is_mounted =
+            |name: &str| !name.is_empty() && self.connected_disks.iter().any(|d| d.matches(name));
+
+        let disk_name = if is_mounted(&self.target_name) {
+            self.target_name.clone()
+        } else if is_mounted(MAINTENANCE_DISK_NAME) {
+            MAINTENANCE_DISK_NAME.to_string()
+        } else {
+            self.log_widget.push(LogType::Error(format!(
+                "No DAPLink drive mounted ('{}' or '{MAINTENANCE_DISK_NAME}')",
+                self.target_name
+            )));
+            return Task::none();
+        };
+
+        let remount_name = match self.daplink_command {
+            DaplinkCommand::StartBootloader => MAINTENANCE_DISK_NAME.to_string(),
+            DaplinkCommand::StartInterface => self.target_name.clone(),
+            _ => disk_name.clone(),
+        };
+
+        if remount_name.is_empty() {
+            self.log_widget
+                .push(LogType::Error("The target mount name is empty.".into()));
+            return Task::none();
+        }
+
+        self.log_widget.push(LogType::InfoNoPrefix("\n\n".into()));
+        self.log_widget.push(LogType::Info(format!(
+            "Send {} to {disk_name}",
+            self.daplink_command.filename()
+        )));
+        self.is_readonly = true;
+
+        Task::perform(
+            disk_tool::send_daplink_command(
+                disk_name,
+                remount_name,
+                self.daplink_command,
+                Duration::from_secs(self.target_waiting_time),
+            ),
+            |x| Message::DapLink(TabDaplinkMessage::DoneDaplinkCommand(x)),
+        )
+    }
+
+    /// Logs the content of `DETAILS.TXT` and checks it against the image that
+    /// was just installed. Returns `None` (and logs why) on any failure.
+    fn check_details(
+        &mut self,
+        result: Result<DaplinkDetails, String>,
+        expected_mode: &str,
+        image: Option<PathBuf>,
+    ) -> Option<DaplinkDetails> {
+        let details = match result {
+            Ok(details) => details,
+            Err(e) => {
+                self.log_widget.push(LogType::Error(e));
+                return None;
+            }
+        };
+
+        for line in details.summary() {
+            self.log_widget.push(LogType::Info(format!("    {line}")));
+        }
+        if let (Some(run), Some(unique_id)) = (self.run.as_mut(), details.unique_id()) {
+            run.set_unique_id(unique_id);
+        }
+
+        let image = match image {
+            Some(image) => image,
+            None => {
+                return match details.verify_mode(expected_mode) {
+                    Ok(_) => Some(details),
+                    Err(e) => {
+                        self.log_widget.push(LogType::Error(e));
+                        None
+                    }
+                }
+            }
+        };
+
+        match details.verify_image(expected_mode, &image) {
+            Ok(_) => {
+                self.log_widget.push(LogType::Info(format!(
+                    "{expected_mode} matches '{}'",
+                    image
+                        .file_name()
+                        .and_then(|f| f.to_str())
+                        .unwrap_or("undefined")
+                )));
+                Some(details)
+            }
+            Err(e) => {
+                self.log_widget.push(LogType::Error(e));
+                None
+            }
+        }
+    }
+
+    /// Checks the target mount name before anything is flashed, instead of
+    /// finding out with a timeout at the end of the sequence.
+    fn validate_target_name(&mut self) -> bool {
+        let name = self.target_name.trim();
+
+        if name.is_empty() {
+            self.log_widget
+                .push(LogType::Error("The target mount name is empty.".to_owned()));
+            return false;
+        }
+
+        if let Some(disk) = self
+            .connected_disks
+            .iter()
+            .find(|d| d.matches(name) && !d.is_removable)
+        {
+            self.log_widget.push(LogType::Error(format!(
+                "'{name}' is a non removable drive ({}), not a DAPLink target.",
+                disk.path.to_str().unwrap_or("undefined")
+            )));
+            return false;
+        }
+
+        if disk_tool::is_system_disk_name(name) {
+            self.log_widget.push(LogType::Warning(format!(
+                "'{name}' looks like a system drive, not a DAPLink target."
+            )));
+        } else if !disk_tool::KNOWN_DAPLINK_NAMES.contains(&name)
+            && !self.seen_target_names.iter().any(|n| n == name)
+            && !self.connected_disks.iter().any(|d| d.matches(name))
+        {
+            self.log_widget.push(LogType::Warning(format!(
+                "'{name}' is not a known DAPLink drive and was never seen before, check the spelling."
+            )));
+        }
+
         true
     }
 }
@@ -485,7 +2220,47 @@ impl Default for TabDaplink {
             user_file_path: PathBuf::default(),
             target_waiting_time: 10,
             target_name: String::default(),
+            probe: KnownProbe::default(),
+            host_family: F1Variant::default(),
+            profiles: board_profile::default_profiles(),
+            selected_profile: None,
+            profile_name: String::default(),
+            library: FirmwareLibrary::default(),
+            library_selection: None,
+            library_version: String::default(),
+            library_source: String::default(),
+            flash_mode: FlashMode::default(),
+            copy_options: CopyOptions::default(),
+            relock: false,
+            backup_before_erase: false,
+            seen_target_names: Vec::new(),
+            target_name_options: combo_box::State::default(),
             log_widget: LogWidget::default(),
+            connected_probe: None,
+            daplink_connected: false,
+            swd_target_present: false,
+            is_detecting: false,
+            connected_disks: Vec::new(),
+            daplink_command: DaplinkCommand::default(),
+            previous_bootloader_version: None,
+            unlock_after_backup: false,
+            progress: None,
+            run_passed: false,
+            batch: None,
+            run: None,
+            passed_runs: Vec::new(),
+            target_settings: TargetSettings::default(),
+            device_id: None,
         }
     }
 }
+
+impl std::fmt::Display for FlashMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            FlashMode::Full => "Full (SWD probe + USB)",
+            FlashMode::FirmwareOnly => "Update interface firmware only (USB)",
+            FlashMode::BootloaderUpdate => "Update bootloader through the interface (USB)",
+        })
+    }
+}
@@ -0,0 +1,214 @@
+use std::path::PathBuf;
+
+use iced::{
+    widget::{button, column, pick_list, row, scrollable, text, text_input, Column},
+    Color, Element, Font, Length, Task,
+};
+
+use crate::{
+    run_history::{self, RunRecord},
+    utils,
+};
+
+use super::messages::{Message, TabHistoryMessage};
+
+const TEXT_SIZE: u16 = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StatusFilter {
+    #[default]
+    All,
+    Passed,
+    Failed,
+}
+
+const ALL_STATUS_FILTERS: [StatusFilter; 3] = [
+    StatusFilter::All,
+    StatusFilter::Passed,
+    StatusFilter::Failed,
+];
+
+impl StatusFilter {
+    fn accepts(&self, record: &RunRecord) -> bool {
+        match self {
+            StatusFilter::All => true,
+            StatusFilter::Passed => record.passed,
+            StatusFilter::Failed => !record.passed,
+        }
+    }
+}
+
+impl std::fmt::Display for StatusFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            StatusFilter::All => "All runs",
+            StatusFilter::Passed => "PASS only",
+            StatusFilter::Failed => "FAIL only",
+        })
+    }
+}
+
+/// Past runs of the DapLink tab, read from the history file.
+#[derive(Debug, Default)]
+pub struct TabHistory {
+    records: Vec<RunRecord>,
+    filter: String,
+    status_filter: StatusFilter,
+    /// Result of the last reload or export.
+    message: String,
+}
+
+impl TabHistory {
+    pub fn reload(&mut self) {
+        match run_history::load() {
+            Ok(records) => {
+                self.records = records;
+                self.message.clear();
+            }
+            Err(e) => self.message = e,
+        }
+    }
+
+    fn filtered(&self) -> Vec<&RunRecord> {
+        let filter = self.filter.trim().to_lowercase();
+
+        self.records
+            .iter()
+            .rev()
+            .filter(|r| self.status_filter.accepts(r) && r.matches(&filter))
+            .collect()
+    }
+
+    pub fn update(&mut self, message: TabHistoryMessage) -> Task<Message> {
+        match message {
+            TabHistoryMessage::FilterChanged(s) => self.filter = s,
+            TabHistoryMessage::StatusFilterSelected(f) => self.status_filter = f,
+            TabHistoryMessage::Refresh => self.reload(),
+            TabHistoryMessage::ExportCsv => {
+                return Task::perform(
+                    utils::select_save_file("Export history", "history.csv", "CSV file", "csv"),
+                    |x| Message::History(TabHistoryMessage::SelectExportFile(x)),
+                );
+            }
+            TabHistoryMessage::SelectExportFile(path) => {
+                if let Some(path) = path {
+                    self.export(path);
+                }
+            }
+        }
+
+        Task::none()
+    }
+
+    fn export(&mut self, path: PathBuf) {
+        let records = self.filtered();
+
+        self.message = match run_history::export_csv(&path, &records) {
+            Ok(_) => format!(
+                "{} runs exported to {}",
+                records.len(),
+                path.to_str().unwrap_or("undefined")
+            ),
+            Err(e) => e,
+        };
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let records = self.filtered();
+        let passed = records.iter().filter(|r| r.passed).count();
+
+        let controls = row![
+            text_input(
+                "Filter (date, profile, unique ID, file, hash...)",
+                &self.filter
+            )
+            .on_input(|s| Message::History(TabHistoryMessage::FilterChanged(s)))
+            .width(Length::Fill),
+            pick_list(&ALL_STATUS_FILTERS[..], Some(&self.status_filter), |x| {
+                Message::History(TabHistoryMessage::StatusFilterSelected(x))
+            }),
+            button("Refresh").on_press(Message::History(TabHistoryMessage::Refresh)),
+            button("Export CSV").on_press(Message::History(TabHistoryMessage::ExportCsv)),
+        ]
+        .spacing(8);
+
+        let summary = text(format!(
+            "{} runs: {passed} PASS, {} FAIL. {}",
+            records.len(),
+            records.len() - passed,
+            self.message
+        ))
+        .size(TEXT_SIZE);
+
+        let lines: Vec<Element<Message>> = records.iter().map(|r| view_record(r)).collect();
+
+        column![
+            controls,
+            summary,
+            scrollable(Column::with_children(lines).spacing(8))
+                .height(Length::Fill)
+                .width(Length::Fill)
+        ]
+        .spacing(16)
+        .padding(8)
+        .into()
+    }
+}
+
+fn view_record(record: &RunRecord) -> Element<'_, Message> {
+    let line = |s: String| text(s).size(TEXT_SIZE).font(Font::MONOSPACE);
+
+    let color = if record.passed {
+        Color::from_rgb8(0, 0x88, 0)
+    } else {
+        Color::from_rgb8(0xAA, 0, 0)
+    };
+
+    let header = line(format!(
+        "{} UTC  {}  {:.1} s  {}  {}  {}",
+        run_history::format_timestamp(record.timestamp),
+        record.status(),
+        record.duration_ms as f32 / 1000.0,
+        record.mode,
+        if record.profile.is_empty() {
+            "(no profile)"
+        } else {
+            &record.profile
+        },
+        record.unique_id.as_deref().unwrap_or("(unknown ID)")
+    ))
+    .color(color);
+
+    let steps: Vec<String> = record
+        .steps
+        .iter()
+        .map(|s| {
+            format!(
+                "{}{} {:.1} s",
+                if s.passed { "" } else { "✘ " },
+                s.name,
+                s.duration_ms as f32 / 1000.0
+            )
+        })
+        .collect();
+
+    let mut col = column![
+        header,
+        line(format!("  {}", steps.join(" > "))).shaping(text::Shaping::Advanced)
+    ];
+    if let Some(uid) = &record.device_uid {
+        col = col.push(line(format!(
+            "  SWD UID {uid}, {} KiB, IDCODE {}",
+            record.flash_size_kb.unwrap_or_default(),
+            record.idcode.as_deref().unwrap_or_default()
+        )));
+    }
+    for image in &record.images {
+        col = col.push(line(format!(
+            "  {}: {} {}",
+            image.label, image.file_name, image.sha256
+        )));
+    }
+
+    col.into()
+}
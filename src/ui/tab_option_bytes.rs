@@ -0,0 +1,266 @@
+use iced::{
+    widget::{button, checkbox, column, pick_list, row, scrollable, text, Column},
+    Color, Element, Font, Length, Task,
+};
+
+use crate::{
+    device_id::{F1Variant, SwdTarget, TargetSettings, ALL_SWD_TARGETS},
+    log_entries::LogType,
+    open_ocd_task,
+    option_bytes::OptionBytes,
+    utils,
+};
+
+use super::{
+    log_widget::LogWidget,
+    messages::{Message, TabOptionBytesMessage},
+};
+
+const TEXT_SIZE: u16 = 12;
+
+/// Reads, shows and edits the option bytes of the chip behind the probe.
+#[derive(Debug)]
+pub struct TabOptionBytes {
+    target: SwdTarget,
+    /// As read from the chip.
+    read: Option<OptionBytes>,
+    /// `read` with the user changes.
+    edited: Option<OptionBytes>,
+    /// OpenOCD settings for the F1 variant detected before the last read.
+    settings: TargetSettings,
+    is_busy: bool,
+    log: LogWidget,
+}
+
+impl Default for TabOptionBytes {
+    fn default() -> Self {
+        Self {
+            target: SwdTarget::Stm32F1,
+            read: None,
+            edited: None,
+            settings: TargetSettings::default(),
+            is_busy: false,
+            log: LogWidget::default(),
+        }
+    }
+}
+
+impl TabOptionBytes {
+    pub fn is_busy(&self) -> bool {
+        self.is_busy
+    }
+
+    fn changes(&self) -> Vec<String> {
+        match (&self.read, &self.edited) {
+            (Some(read), Some(edited)) => read.diff(edited),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The F1 clones need their own OpenOCD settings: the chip is
+    /// identified before its option bytes are read.
+    fn read_task(&mut self) -> Task<Message> {
+        self.is_busy = true;
+        self.log.push(LogType::Info(format!(
+            "Read option bytes of {}",
+            self.target
+        )));
+        match self.target {
+            SwdTarget::Stm32F1 => Task::perform(
+                open_ocd_task::read_device_id(self.target, TargetSettings::default()),
+                |x| Message::OptionBytes(TabOptionBytesMessage::DoneReadDeviceId(x)),
+            ),
+            SwdTarget::Stm32Wb => self.read_option_bytes_task(),
+        }
+    }
+
+    fn read_option_bytes_task(&self) -> Task<Message> {
+        Task::perform(
+            open_ocd_task::read_option_bytes(self.target, self.settings),
+            |x| Message::OptionBytes(TabOptionBytesMessage::DoneRead(x)),
+        )
+    }
+
+    pub fn update(&mut self, message: TabOptionBytesMessage) -> Task<Message> {
+        match message {
+            TabOptionBytesMessage::TargetSelected(target) => {
+                self.target = target;
+                self.settings = TargetSettings::default();
+                self.read = None;
+                self.edited = None;
+            }
+            TabOptionBytesMessage::Read => return self.read_task(),
+            TabOptionBytesMessage::DoneReadDeviceId(result) => {
+                self.settings = match result {
+                    Ok(id) => {
+                        let variant = F1Variant::detect(&id);
+                        self.log.push(LogType::Info(format!("Detected {variant}")));
+                        variant.settings(Some(&id))
+                    }
+                    Err(e) => {
+                        self.log.push(LogType::Warning(e));
+                        TargetSettings::default()
+                    }
+                };
+                return self.read_option_bytes_task();
+            }
+            TabOptionBytesMessage::DoneRead(result) => {
+                self.is_busy = false;
+                match result {
+                    Ok(option_bytes) => {
+                        self.read = Some(option_bytes.clone());
+                        self.edited = Some(option_bytes);
+                    }
+                    Err(e) => self.log.push(LogType::Error(e)),
+                }
+            }
+            TabOptionBytesMessage::FieldToggled(index, value) => {
+                if let Some(edited) = self.edited.as_mut() {
+                    let field = &edited.fields()[index];
+                    edited.set(field, u32::from(value));
+                }
+            }
+            TabOptionBytesMessage::Revert => self.edited = self.read.clone(),
+            TabOptionBytesMessage::Write => {
+                let changes = self.changes();
+                if changes.is_empty() {
+                    return Task::none();
+                }
+                let erase_warning = match (&self.read, &self.edited) {
+                    (Some(read), Some(edited)) if read.erases_flash(edited) => {
+                        "\n\nWARNING: removing the read-out protection mass-erases the whole flash."
+                    }
+                    _ => "",
+                };
+                return Task::perform(
+                    utils::confirm(
+                        "Write option bytes".into(),
+                        format!(
+                            "The following option bytes of {} will be changed:\n\n{}{erase_warning}\n\nContinue?",
+                            self.target,
+                            changes.join("\n")
+                        ),
+                    ),
+                    |x| Message::OptionBytes(TabOptionBytesMessage::ConfirmWrite(x)),
+                );
+            }
+            TabOptionBytesMessage::ConfirmWrite(confirmed) => {
+                if let (true, Some(read), Some(edited)) = (confirmed, &self.read, &self.edited) {
+                    let commands = read.write_commands(edited);
+                    for change in read.diff(edited) {
+                        self.log.push(LogType::Info(format!("Write {change}")));
+                    }
+                    self.is_busy = true;
+                    return Task::perform(
+                        open_ocd_task::write_option_bytes(self.target, self.settings, commands),
+                        |x| Message::OptionBytes(TabOptionBytesMessage::DoneWrite(x)),
+                    );
+                }
+            }
+            TabOptionBytesMessage::DoneWrite(result) => {
+                self.is_busy = false;
+                match result {
+                    Ok(result) => {
+                        self.log.from_log_entries(&result.log);
+                        match result.code {
+                            // Read back what the chip actually took
+                            Some(0) => return self.read_task(),
+                            Some(code) => {
+                                self.log.push(LogType::Error(format!("Exit code: {code}")))
+                            }
+                            None => self
+                                .log
+                                .push(LogType::Error("Process terminated by signal.".into())),
+                        }
+                    }
+                    Err(e) => self.log.push(LogType::Error(format!(
+                        "Failed to write the option bytes. Error: {e}"
+                    ))),
+                }
+            }
+        }
+
+        Task::none()
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let has_changes = !self.changes().is_empty();
+
+        let controls = row![
+            pick_list(&ALL_SWD_TARGETS[..], Some(&self.target), |x| {
+                Message::OptionBytes(TabOptionBytesMessage::TargetSelected(x))
+            })
+            .width(Length::Fill),
+            button("Read").on_press_maybe(
+                (!self.is_busy).then_some(Message::OptionBytes(TabOptionBytesMessage::Read))
+            ),
+            button("Revert").on_press_maybe(
+                has_changes.then_some(Message::OptionBytes(TabOptionBytesMessage::Revert))
+            ),
+            button("Write changes")
+                .style(button::danger)
+                .on_press_maybe(
+                    (has_changes && !self.is_busy)
+                        .then_some(Message::OptionBytes(TabOptionBytesMessage::Write))
+                ),
+        ]
+        .spacing(8);
+
+        let fields: Element<Message> = match (&self.read, &self.edited) {
+            (Some(read), Some(edited)) => {
+                let rows: Vec<Element<Message>> = edited
+                    .fields()
+                    .iter()
+                    .enumerate()
+                    .map(|(index, field)| {
+                        let color = if read.get(field) != edited.get(field) {
+                            Color::from_rgb8(0xAB, 0x69, 0)
+                        } else {
+                            Color::from_rgb8(0x80, 0x80, 0x80)
+                        };
+
+                        let value: Element<Message> = if field.editable && !self.is_busy {
+                            checkbox(edited.decode(field), edited.get(field) == 1)
+                                .on_toggle(move |x| {
+                                    Message::OptionBytes(TabOptionBytesMessage::FieldToggled(
+                                        index, x,
+                                    ))
+                                })
+                                .text_size(TEXT_SIZE)
+                                .into()
+                        } else {
+                            text(edited.decode(field))
+                                .size(TEXT_SIZE)
+                                .font(Font::MONOSPACE)
+                                .into()
+                        };
+
+                        row![
+                            text(field.name)
+                                .size(TEXT_SIZE)
+                                .font(Font::MONOSPACE)
+                                .width(100),
+                            column![value].width(200),
+                            text(field.description).size(TEXT_SIZE).color(color),
+                        ]
+                        .spacing(8)
+                        .into()
+                    })
+                    .collect();
+
+                scrollable(Column::with_children(rows).spacing(6))
+                    .height(Length::FillPortion(2))
+                    .width(Length::Fill)
+                    .into()
+            }
+            _ => text("Connect the probe and press \"Read\".")
+                .size(TEXT_SIZE)
+                .into(),
+        };
+
+        column![controls, fields, self.log.view()]
+            .spacing(16)
+            .padding(8)
+            .into()
+    }
+}
@@ -17,25 +17,31 @@ use iced::{
     },
     stream::channel,
     widget::{button, center, column, container, opaque, pick_list, row, stack, text},
-    Element, Length, Task, Theme,
+    Color, Element, Length, Task, Theme,
 };
 use iced_aw::{grid, grid_row};
 use serde::{Deserialize, Serialize};
 use serialport::{SerialPort, SerialPortType};
 
 use crate::{
-    dirs,
+    device_id::{SwdTarget, TargetSettings},
+    dirs, firmware_library,
+    hotplug::DeviceSnapshot,
     log_entries::{LogEntries, LogType},
     open_ocd_task,
     operator_tool::{
         operator_error_string, upgrade_status_string, OperatorResult, OperatorVersionResult,
     },
+    progress::Progress,
+    run_history::{self, RunRecorder},
     stackfile_config::{fus_config, wireless_stack_config, FusFile, WirelessStackFile},
+    usb_ids::KnownUsbDevice,
 };
 
 use super::{
     log_widget::LogWidget,
     messages::{Message, TabWsMessage},
+    progress_widget,
 };
 
 const DELETE_CMD: &[u8] = "DELETE\n".as_bytes();
@@ -47,18 +53,49 @@ const VERSION_CMD: &[u8] = "VERSION\n".as_bytes();
 pub enum FwStep {
     #[default]
     Ready,
+    /// Same as `Ready`, once the whole sequence succeeded.
+    Done,
     StartProcess,
     StepFlashOperator,
+    StepVerifyOperator,
+    StepDetectOperator,
     StepUpgradeFUS,
     StepFlashFUS(String),
+    StepVerifyFUS,
+    StepInstallFUS,
     StepDeleteFW,
     StepFlashFW,
+    StepVerifyFW,
+    StepInstallFW,
 }
 
+impl FwStep {
+    /// Position in the sequence and name shown by the progress indicator.
+    fn progress_step(&self) -> Option<(usize, &'static str)> {
+        match self {
+            FwStep::Ready | FwStep::Done | FwStep::StartProcess => None,
+            FwStep::StepFlashOperator => Some((1, "Flash operator")),
+            FwStep::StepVerifyOperator => Some((2, "Verify operator")),
+            FwStep::StepDetectOperator => Some((3, "Detect operator")),
+            FwStep::StepUpgradeFUS => Some((4, "Check FUS version")),
+            FwStep::StepFlashFUS(_) => Some((5, "Flash FUS")),
+            FwStep::StepVerifyFUS => Some((6, "Verify FUS")),
+            FwStep::StepInstallFUS => Some((7, "Install FUS")),
+            FwStep::StepDeleteFW => Some((8, "Delete wireless stack")),
+            FwStep::StepFlashFW => Some((9, "Flash wireless stack")),
+            FwStep::StepVerifyFW => Some((10, "Verify wireless stack")),
+            FwStep::StepInstallFW => Some((11, "Install wireless stack")),
+        }
+    }
+}
+
+const STEP_COUNT: usize = 11;
+
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct SerialPortInfo {
     port: String,
     product: Option<String>,
+    device: Option<KnownUsbDevice>,
 }
 
 const MAX_FUS_UPGRADE_ATTEMPTS: u32 = 3;
@@ -70,12 +107,20 @@ pub struct TabWirelessStack {
     serial_available_port: Vec<SerialPortInfo>,
     #[serde(skip)]
     serial_selected: Option<SerialPortInfo>,
+    /// Set once the operator answered a STATUS on `serial_selected`.
+    #[serde(skip)]
+    serial_confirmed: bool,
     #[serde(skip)]
     log: LogWidget,
     #[serde(skip)]
     is_readonly: bool,
     #[serde(skip)]
     fus_upgrade_attempts: u32,
+    #[serde(skip)]
+    progress: Option<Progress>,
+    /// Record of the run in progress, appended to the history at the end.
+    #[serde(skip)]
+    run: Option<RunRecorder>,
 }
 
 const ALL_STACK: [WirelessStackFile; 21] = [
@@ -133,6 +178,7 @@ impl TabWirelessStack {
                 ]
                 .spacing(8)
             ),
+            grid_row!("", self.view_serial_status()),
         )
         .width(Length::Fill)
         .column_spacing(8)
@@ -157,6 +203,11 @@ impl TabWirelessStack {
 
         let main_col = column![grid_fields, start_button].padding(8).spacing(16);
 
+        let progress = match &self.progress {
+            Some(progress) => progress_widget::view(progress),
+            None => row![].into(),
+        };
+
         let layout = if self.is_readonly {
             column![
                 stack![
@@ -170,6 +221,7 @@ impl TabWirelessStack {
                         }
                     }))
                 ],
+                progress,
                 log
             ]
         } else {
@@ -179,28 +231,98 @@ impl TabWirelessStack {
         layout.into()
     }
 
+    fn view_serial_status(&self) -> Element<'_, Message> {
+        let status = match self.serial_selected.as_ref() {
+            Some(serial) if self.serial_confirmed => {
+                text(format!("✔ Operator answered on {}", serial.port))
+                    .color(Color::from_rgb8(0, 0x88, 0))
+            }
+            Some(SerialPortInfo {
+                device: Some(device),
+                ..
+            }) => text(format!("{device} detected")),
+            Some(_) => text("Unknown device, check the selected port")
+                .color(Color::from_rgb8(0xAB, 0x69, 0)),
+            None => text("No serial port").color(Color::from_rgb8(0xAA, 0, 0)),
+        };
+
+        status.size(12).shaping(text::Shaping::Advanced).into()
+    }
+
     pub fn update(&mut self, message: TabWsMessage) -> Task<Message> {
         match message {
             TabWsMessage::StackSelected(file) => self.fw_selected = file,
-            TabWsMessage::SerialSelected(serial) => self.serial_selected = Some(serial),
+            TabWsMessage::SerialSelected(serial) => {
+                self.serial_selected = Some(serial);
+                self.serial_confirmed = false;
+            }
+            TabWsMessage::SerialDetected(serial) => {
+                self.log.push(LogType::Info(format!(
+                    "Operator answered on {serial}, port selected"
+                )));
+                self.serial_selected = Some(serial);
+                self.serial_confirmed = true;
+            }
             TabWsMessage::SerialRefresh => {
                 self.refresh_serial_ports();
             }
             TabWsMessage::StepChange(next_step) => {
+                if let Some((step, name)) = next_step.progress_step() {
+                    self.progress
+                        .get_or_insert_with(|| Progress::new(Some(STEP_COUNT), Vec::new()))
+                        .set_step(step, name);
+                    if let Some(run) = self.run.as_mut() {
+                        run.begin_step(name);
+                    }
+                }
+
                 return match next_step {
-                    FwStep::Ready => {
+                    FwStep::Ready | FwStep::Done => {
                         self.is_readonly = false;
+                        self.progress = None;
+                        self.finish_run(matches!(next_step, FwStep::Done));
                         Task::none()
                     }
                     FwStep::StartProcess => self.step_start_process(),
                     FwStep::StepFlashOperator => self.step_flash_operator(),
+                    FwStep::StepVerifyOperator => self.step_verify(
+                        "Verify operator",
+                        "wb55_operator.hex",
+                        FwStep::StepDetectOperator,
+                    ),
+                    FwStep::StepDetectOperator => self.step_detect_operator(),
                     FwStep::StepUpgradeFUS => self.step_upgrade_fus(),
                     FwStep::StepFlashFUS(file) => self.step_flash_fus(file),
+                    FwStep::StepVerifyFUS => {
+                        self.step_verify("Verify FUS", "merge.hex", FwStep::StepInstallFUS)
+                    }
+                    FwStep::StepInstallFUS => self.step_install(FwStep::StepUpgradeFUS),
                     FwStep::StepDeleteFW => self.step_delete_fw(),
                     FwStep::StepFlashFW => self.step_flash_fw(),
+                    FwStep::StepVerifyFW => self.step_verify(
+                        "Verify wireless stack",
+                        "merge.hex",
+                        FwStep::StepInstallFW,
+                    ),
+                    FwStep::StepInstallFW => self.step_install(FwStep::Done),
                 };
             }
-            TabWsMessage::LogMessage(log) => self.log.push(log),
+            TabWsMessage::DeviceIdRead(id) => {
+                if let Some(run) = self.run.as_mut() {
+                    run.set_device_id(&id);
+                }
+            }
+            TabWsMessage::UpgradeStatus(status) => {
+                if let Some(progress) = self.progress.as_mut() {
+                    progress.feed_fus_status(status);
+                }
+            }
+            TabWsMessage::LogMessage(log) => {
+                if let (Some(progress), LogType::Info(line)) = (self.progress.as_mut(), &log) {
+                    progress.feed_openocd(line);
+                }
+                self.log.push(log);
+            }
             TabWsMessage::LogMessages(entries) => self.log.from_log_entries(&entries),
         }
 
@@ -219,6 +341,7 @@ impl TabWirelessStack {
         self.fus_upgrade_attempts = 0;
         self.log
             .push(LogType::Info("Start flashing...".to_string()));
+        self.start_run();
 
         let serial = self.serial_selected.as_ref().unwrap().clone();
         Self::message_runner(|mut o| async move {
@@ -233,11 +356,22 @@ impl TabWirelessStack {
         self.log.push(LogType::Info("Flash operator".to_string()));
 
         Self::message_runner(|mut o| async move {
+            match open_ocd_task::read_device_id(SwdTarget::Stm32Wb, TargetSettings::default()).await
+            {
+                Ok(id) => {
+                    for line in id.summary() {
+                        Self::send_log(&mut o, LogType::Info(line)).await;
+                    }
+                    let _ = o.send(TabWsMessage::DeviceIdRead(id)).await;
+                }
+                Err(e) => Self::send_log(&mut o, LogType::Warning(e)).await,
+            }
+
             match open_ocd_task::flash_wb55("wb55_operator.hex", &mut o).await {
                 Ok(result) => match result.code {
                     Some(0) => {
                         Self::send_logs(&mut o, result.log).await;
-                        Self::send_step(&mut o, FwStep::StepUpgradeFUS).await;
+                        Self::send_step(&mut o, FwStep::StepVerifyOperator).await;
                     }
                     Some(code) => {
                         Self::send_log(
@@ -261,6 +395,52 @@ impl TabWirelessStack {
         })
     }
 
+    /// Sends STATUS to the selected port, then to every other port with a
+    /// known USB identity, and selects the first one the operator answers
+    /// on. Ports of unknown devices are never probed.
+    fn step_detect_operator(&mut self) -> Task<Message> {
+        self.log
+            .push(LogType::Info("Detect operator serial port".to_string()));
+
+        let mut candidates: Vec<SerialPortInfo> = self.serial_selected.iter().cloned().collect();
+        candidates.extend(
+            self.serial_available_port
+                .iter()
+                .filter(|p| p.device.is_some() && Some(*p) != self.serial_selected.as_ref())
+                .cloned(),
+        );
+
+        Self::message_runner(|mut o| async move {
+            Timer::after(Duration::from_secs(1)).await;
+
+            let mut detected = None;
+            for candidate in candidates {
+                if Self::probe_operator(&candidate.port).await {
+                    detected = Some(candidate);
+                    break;
+                }
+            }
+
+            match detected {
+                Some(serial) => {
+                    let _ = o.send(TabWsMessage::SerialDetected(serial)).await;
+                }
+                None => {
+                    Self::send_log(
+                        &mut o,
+                        LogType::Warning(
+                            "The operator did not answer on any port, keep the selected one."
+                                .into(),
+                        ),
+                    )
+                    .await
+                }
+            }
+
+            Self::send_step(&mut o, FwStep::StepUpgradeFUS).await;
+        })
+    }
+
     fn step_upgrade_fus(&mut self) -> Task<Message> {
         self.log.push(LogType::Info("FUS update".to_string()));
 
@@ -348,46 +528,13 @@ impl TabWirelessStack {
             self.fus_upgrade_attempts, MAX_FUS_UPGRADE_ATTEMPTS
         )));
 
-        let serial = self.serial_selected.as_ref().unwrap().clone();
         Self::message_runner(|mut o| async move {
             if let Err(e) = Self::prepare_merged_hex(&file) {
                 Self::error_handle(&mut o, e).await;
                 return;
             }
 
-            match open_ocd_task::flash_wb55("merge.hex", &mut o).await {
-                Ok(result) => match result.code {
-                    Some(0) => Self::send_logs(&mut o, result.log).await,
-                    Some(code) => {
-                        Self::send_log(
-                            &mut o,
-                            LogType::Error(format!("Flash failed (exit code {code})")),
-                        )
-                        .await;
-                        Self::send_step(&mut o, FwStep::Ready).await;
-                        return;
-                    }
-                    None => {
-                        Self::send_log(
-                            &mut o,
-                            LogType::Error("Flash failed: OpenOCD terminated by signal".into()),
-                        )
-                        .await;
-                        Self::send_step(&mut o, FwStep::Ready).await;
-                        return;
-                    }
-                },
-                Err(e) => {
-                    Self::error_handle(&mut o, e).await;
-                    return;
-                }
-            }
-
-            Self::send_log(&mut o, LogType::Info("Send UPGRADE command".into())).await;
-            match Self::fus_upgrade_cmd(&serial, &mut o).await {
-                Ok(_) => Self::send_step(&mut o, FwStep::StepUpgradeFUS).await,
-                Err(e) => Self::error_handle(&mut o, e).await,
-            }
+            Self::flash_merged_hex(&mut o, FwStep::StepVerifyFUS).await;
         })
     }
 
@@ -452,7 +599,6 @@ impl TabWirelessStack {
         self.log
             .push(LogType::Info("Flash wireless stack".to_string()));
 
-        let serial = self.serial_selected.as_ref().unwrap().clone();
         let fw = wireless_stack_config(self.fw_selected);
         Self::message_runner(move |mut o| async move {
             if let Err(e) = Self::prepare_merged_hex(fw) {
@@ -460,49 +606,83 @@ impl TabWirelessStack {
                 return;
             }
 
-            match open_ocd_task::flash_wb55("merge.hex", &mut o).await {
-                Ok(result) => match result.code {
-                    Some(0) => Self::send_logs(&mut o, result.log).await,
-                    Some(code) => {
-                        Self::send_log(
-                            &mut o,
-                            LogType::Error(format!("Flash failed (exit code {code})")),
-                        )
-                        .await;
-                        Self::send_step(&mut o, FwStep::Ready).await;
-                        return;
-                    }
-                    None => {
+            Self::flash_merged_hex(&mut o, FwStep::StepVerifyFW).await;
+        })
+    }
+
+    /// Reads back `file` once flashed, in a step of its own.
+    fn step_verify(&mut self, title: &str, file: &'static str, next: FwStep) -> Task<Message> {
+        self.log.push(LogType::Info(title.to_string()));
+
+        Self::message_runner(move |mut o| async move {
+            if Self::verify_flash(&mut o, file).await {
+                Self::send_step(&mut o, next).await;
+            }
+        })
+    }
+
+    /// Sends UPGRADE so that FUS installs what was just flashed.
+    fn step_install(&mut self, next: FwStep) -> Task<Message> {
+        self.log
+            .push(LogType::Info("Send UPGRADE command".to_string()));
+
+        let serial = self.serial_selected.as_ref().unwrap().clone();
+        Self::message_runner(move |mut o| async move {
+            match Self::fus_upgrade_cmd(&serial, &mut o).await {
+                Ok(_) => {
+                    if matches!(next, FwStep::Done) {
                         Self::send_log(
                             &mut o,
-                            LogType::Error("Flash failed: OpenOCD terminated by signal".into()),
+                            LogType::Info("Wireless stack is now flashed !".into()),
                         )
                         .await;
-                        Self::send_step(&mut o, FwStep::Ready).await;
-                        return;
                     }
-                },
-                Err(e) => {
-                    Self::error_handle(&mut o, e).await;
-                    return;
-                }
-            };
-
-            Self::send_log(&mut o, LogType::Info("Send UPGRADE command".into())).await;
-            match Self::fus_upgrade_cmd(&serial, &mut o).await {
-                Ok(_) => {
-                    Self::send_log(
-                        &mut o,
-                        LogType::Info("Wireless stack is now flashed !".into()),
-                    )
-                    .await;
-                    Self::send_step(&mut o, FwStep::Ready).await;
+                    Self::send_step(&mut o, next).await;
                 }
                 Err(e) => Self::error_handle(&mut o, e).await,
             }
         })
     }
 
+    /// Flashes `<tmp>/merge.hex`, then goes on with `next`.
+    async fn flash_merged_hex(sender: &mut Sender<TabWsMessage>, next: FwStep) {
+        match open_ocd_task::flash_wb55("merge.hex", sender).await {
+            Ok(result) => match result.code {
+                Some(0) => {
+                    Self::send_logs(sender, result.log).await;
+                    Self::send_step(sender, next).await;
+                }
+                Some(code) => {
+                    Self::error_handle(sender, format!("Flash failed (exit code {code})")).await
+                }
+                None => {
+                    Self::error_handle(sender, "Flash failed: OpenOCD terminated by signal".into())
+                        .await
+                }
+            },
+            Err(e) => Self::error_handle(sender, e).await,
+        }
+    }
+
+    /// Starts recording the run, with the SHA-256 of the selected stack.
+    fn start_run(&mut self) {
+        let mut run = RunRecorder::new("", "Wireless stack");
+        let file = wireless_stack_config(self.fw_selected);
+        match Self::path_ws_file(file).and_then(|path| firmware_library::sha256_file(&path)) {
+            Ok(sha256) => run.add_image("Wireless stack", file, &sha256),
+            Err(e) => self.log.push(LogType::Warning(e)),
+        }
+        self.run = Some(run);
+    }
+
+    fn finish_run(&mut self, passed: bool) {
+        if let Some(run) = self.run.take() {
+            if let Err(e) = run_history::append(&run.finish(passed)) {
+                self.log.push(LogType::Warning(e));
+            }
+        }
+    }
+
     fn message_runner<F>(f: impl FnOnce(mpsc::Sender<TabWsMessage>) -> F + 'static) -> Task<Message>
     where
         F: Future<Output = ()> + std::marker::Send + 'static,
@@ -571,6 +751,26 @@ impl TabWirelessStack {
         Self::send_step(sender, FwStep::Ready).await;
     }
 
+    /// Compares what `flash_wb55` read back with `file`, a mismatch stops
+    /// the process.
+    async fn verify_flash(sender: &mut Sender<TabWsMessage>, file: &str) -> bool {
+        let result = match open_ocd_task::wb55_image_path(file) {
+            Ok(path) => open_ocd_task::verify_image(path).await,
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(summary) => {
+                Self::send_log(sender, LogType::Info(summary)).await;
+                true
+            }
+            Err(e) => {
+                Self::error_handle(sender, format!("Verify failed ({e})")).await;
+                false
+            }
+        }
+    }
+
     fn path_ws_file(filename: &str) -> Result<PathBuf, String> {
         let mut path = dirs::get_wireless_stack_dir()?;
         path.push(filename);
@@ -590,39 +790,84 @@ impl TabWirelessStack {
     }
 
     pub fn refresh_serial_ports(&mut self) {
-        let ports = serialport::available_ports();
+        if let Ok(ports) = serialport::available_ports() {
+            self.set_serial_ports(ports);
+        } else {
+            self.serial_available_port.clear();
+            self.serial_selected = None;
+            self.serial_confirmed = false;
+        }
+    }
 
+    /// Live update from the hot-plug watcher. The selection is frozen while
+    /// an operation runs: the board may re-enumerate while being flashed.
+    pub fn devices_changed(&mut self, snapshot: &DeviceSnapshot) {
+        if self.is_readonly {
+            let selected = self.serial_selected.clone();
+            let confirmed = self.serial_confirmed;
+            self.set_serial_ports(snapshot.serial_ports.clone());
+            self.serial_selected = selected;
+            self.serial_confirmed = confirmed;
+        } else {
+            self.set_serial_ports(snapshot.serial_ports.clone());
+        }
+    }
+
+    fn set_serial_ports(&mut self, ports: Vec<serialport::SerialPortInfo>) {
+        let previous = self.serial_selected.take();
+        let was_confirmed = self.serial_confirmed;
         self.serial_available_port.clear();
-        self.serial_selected = None;
+        self.serial_confirmed = false;
 
-        if let Ok(ports) = ports {
-            for p in ports {
-                let mut port_helper = SerialPortInfo {
-                    port: p.port_name,
-                    product: None,
-                };
+        for p in ports {
+            let mut port_helper = SerialPortInfo {
+                port: p.port_name,
+                product: None,
+                device: None,
+            };
 
-                if let SerialPortType::UsbPort(type_port) = p.port_type {
-                    if let Some(product) = type_port.product {
-                        port_helper.product = Some(product);
-                    }
+            if let SerialPortType::UsbPort(type_port) = p.port_type {
+                port_helper.device = KnownUsbDevice::identify(
+                    type_port.vid,
+                    type_port.pid,
+                    type_port.product.as_deref(),
+                );
+                if let Some(product) = type_port.product {
+                    port_helper.product = Some(product);
                 }
-
-                self.serial_available_port.push(port_helper);
             }
 
-            self.serial_available_port.sort_by(|a, b| {
-                if a.product.is_none() && b.product.is_some() {
-                    return Ordering::Greater;
-                } else if a.product.is_some() && b.product.is_none() {
-                    return Ordering::Less;
-                } else {
-                    a.port.cmp(&b.port)
+            self.serial_available_port.push(port_helper);
+        }
+
+        // Known devices first (STeaMi, DAPLink then ST-Link), then the
+        // other USB ports, then everything else.
+        self.serial_available_port
+            .sort_by(|a, b| match (a.device, b.device) {
+                (Some(a_dev), Some(b_dev)) if a_dev != b_dev => a_dev.cmp(&b_dev),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                _ => {
+                    if a.product.is_none() && b.product.is_some() {
+                        Ordering::Greater
+                    } else if a.product.is_some() && b.product.is_none() {
+                        Ordering::Less
+                    } else {
+                        a.port.cmp(&b.port)
+                    }
                 }
             });
 
-            self.serial_selected = self.serial_available_port.first().cloned();
-        }
+        // Keep the user's choice if the port is still there, otherwise
+        // fall back on the best candidate.
+        let kept = previous.and_then(|prev| {
+            self.serial_available_port
+                .iter()
+                .find(|p| p.port == prev.port)
+                .cloned()
+        });
+        self.serial_confirmed = was_confirmed && kept.is_some();
+        self.serial_selected = kept.or_else(|| self.serial_available_port.first().cloned());
     }
 
     fn open_port(port: &str) -> serialport::Result<Box<dyn SerialPort>> {
@@ -635,6 +880,20 @@ impl TabWirelessStack {
             .open()
     }
 
+    /// True if something answering like the operator (a JSON
+    /// `OperatorResult`) replies to STATUS on this port.
+    async fn probe_operator(port: &str) -> bool {
+        let mut port = match Self::open_port(port) {
+            Ok(port) => port,
+            Err(_) => return false,
+        };
+
+        match Self::send_and_read_serial(&mut port, STATUS_CMD, None, None).await {
+            Ok(line) => Self::parse_result::<OperatorResult>(&line).is_ok(),
+            Err(_) => false,
+        }
+    }
+
     fn test_serial_port(port: &str) -> Result<(), String> {
         match Self::open_port(port) {
             Ok(_) => Ok(()),
@@ -782,6 +1041,9 @@ impl TabWirelessStack {
         loop {
             let line = Self::read_line(&mut port, Some(Duration::from_secs(10)))?;
             let result: OperatorResult = Self::parse_result(&line)?;
+            let _ = sender
+                .send(TabWsMessage::UpgradeStatus(result.status))
+                .await;
 
             Self::send_log(
                 sender,
@@ -820,19 +1082,24 @@ impl Default for TabWirelessStack {
             fw_selected: Default::default(),
             serial_available_port: Default::default(),
             serial_selected: Default::default(),
+            serial_confirmed: false,
             log: Default::default(),
             is_readonly: false,
             fus_upgrade_attempts: 0,
+            progress: None,
+            run: None,
         }
     }
 }
 
 impl std::fmt::Display for SerialPortInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if let Some(product) = self.product.as_ref() {
-            f.write_str(&format!("{} - {}", &self.port, product))
-        } else {
-            f.write_str(&self.port)
+        match (self.product.as_ref(), self.device) {
+            (Some(product), Some(device)) => {
+                f.write_str(&format!("{} - {} [{}]", &self.port, product, device))
+            }
+            (Some(product), None) => f.write_str(&format!("{} - {}", &self.port, product)),
+            (None, _) => f.write_str(&self.port),
         }
     }
 }
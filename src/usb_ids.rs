@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+
+/// Arm Mbed / DAPLink vendor ID.
+pub const DAPLINK_VID: u16 = 0x0D28;
+/// DAPLink CMSIS-DAP interface (HID + CDC + MSC).
+pub const DAPLINK_PID: u16 = 0x0204;
+/// STMicroelectronics vendor ID.
+pub const ST_VID: u16 = 0x0483;
+/// ST-Link V2-1 and V3 product IDs exposing a virtual COM port.
+pub const STLINK_VCP_PIDS: [u16; 5] = [0x374B, 0x374E, 0x374F, 0x3752, 0x3753];
+/// ST-Link V2 (and its clones), no virtual COM port.
+pub const STLINK_V2_PID: u16 = 0x3748;
+
+/// A device currently plugged on the USB bus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsbDevice {
+    pub vid: u16,
+    pub pid: u16,
+    pub product: Option<String>,
+}
+
+/// SWD probes OpenOCD can program the interface MCU with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum KnownProbe {
+    #[default]
+    StLink,
+    CmsisDap,
+}
+
+pub const ALL_KNOWN_PROBES: [KnownProbe; 2] = [KnownProbe::StLink, KnownProbe::CmsisDap];
+
+impl KnownProbe {
+    pub fn identify(device: &UsbDevice) -> Option<Self> {
+        if device.vid == ST_VID
+            && (device.pid == STLINK_V2_PID || STLINK_VCP_PIDS.contains(&device.pid))
+        {
+            Some(KnownProbe::StLink)
+        } else if device
+            .product
+            .as_deref()
+            .is_some_and(|p| p.contains("CMSIS-DAP"))
+        {
+            // CMSIS-DAP probes are required to carry it in their product name
+            Some(KnownProbe::CmsisDap)
+        } else {
+            None
+        }
+    }
+
+    /// Interface configuration and transport, given before the target one.
+    pub fn openocd_args(&self) -> [&'static str; 4] {
+        match self {
+            KnownProbe::StLink => [
+                "-f",
+                "interface/stlink.cfg",
+                "-c",
+                "transport select hla_swd",
+            ],
+            KnownProbe::CmsisDap => [
+                "-f",
+                "interface/cmsis-dap.cfg",
+                "-c",
+                "transport select swd",
+            ],
+        }
+    }
+}
+
+/// Lists the USB devices. On Linux the whole bus is read from sysfs; other
+/// systems only expose the devices that have a serial port.
+pub fn list_usb_devices() -> Vec<UsbDevice> {
+    #[cfg(target_os = "linux")]
+    {
+        list_usb_devices_sysfs()
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        serialport::available_ports()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|p| match p.port_type {
+                serialport::SerialPortType::UsbPort(usb) => Some(UsbDevice {
+                    vid: usb.vid,
+                    pid: usb.pid,
+                    product: usb.product,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Reads a sysfs attribute holding a hex number (`idVendor`, `idProduct`).
+#[cfg(target_os = "linux")]
+pub fn read_sysfs_hex(path: &std::path::Path) -> Option<u16> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|s| u16::from_str_radix(s.trim(), 16).ok())
+}
+
+#[cfg(target_os = "linux")]
+fn list_usb_devices_sysfs() -> Vec<UsbDevice> {
+    use std::fs;
+
+    let mut result = Vec::new();
+    let entries = match fs::read_dir("/sys/bus/usb/devices") {
+        Ok(entries) => entries,
+        Err(_) => return result,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if let (Some(vid), Some(pid)) = (
+            read_sysfs_hex(&path.join("idVendor")),
+            read_sysfs_hex(&path.join("idProduct")),
+        ) {
+            result.push(UsbDevice {
+                vid,
+                pid,
+                product: fs::read_to_string(path.join("product"))
+                    .ok()
+                    .map(|s| s.trim().to_string()),
+            });
+        }
+    }
+
+    result
+}
+
+/// USB devices the tool knows how to talk to, ordered by preference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum KnownUsbDevice {
+    Steami,
+    DapLink,
+    StLink,
+}
+
+impl KnownUsbDevice {
+    /// Identifies a device from its USB descriptor. The product string is
+    /// checked first since the STeaMi reuses the DAPLink VID/PID.
+    pub fn identify(vid: u16, pid: u16, product: Option<&str>) -> Option<Self> {
+        let product = product.unwrap_or_default().to_lowercase();
+
+        if product.contains("steami") {
+            Some(KnownUsbDevice::Steami)
+        } else if (vid == DAPLINK_VID && pid == DAPLINK_PID) || product.contains("daplink") {
+            Some(KnownUsbDevice::DapLink)
+        } else if (vid == ST_VID && STLINK_VCP_PIDS.contains(&pid)) || product.contains("st-link") {
+            Some(KnownUsbDevice::StLink)
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Display for KnownUsbDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            KnownUsbDevice::Steami => "STeaMi",
+            KnownUsbDevice::DapLink => "DAPLink",
+            KnownUsbDevice::StLink => "ST-Link",
+        })
+    }
+}
+
+impl std::fmt::Display for KnownProbe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            KnownProbe::StLink => "ST-Link",
+            KnownProbe::CmsisDap => "CMSIS-DAP",
+        })
+    }
+}
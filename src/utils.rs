@@ -1,14 +1,28 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, thread};
 
-use rfd::AsyncFileDialog;
+use iced::futures::channel::oneshot;
 
-pub async fn select_file(current: PathBuf, title: &str, allow_hex: bool) -> Option<PathBuf> {
-    let mut dialog = AsyncFileDialog::new()
-        .set_title(title)
-        .add_filter("Binary file (*.bin)", &["bin", "BIN"]);
+use crate::firmware_image::ImageFormat;
+
+use rfd::{AsyncFileDialog, AsyncMessageDialog, MessageButtons, MessageDialogResult, MessageLevel};
 
-    if allow_hex {
-        dialog = dialog.add_filter("Hex file (*.hex)", &["hex", "HEX"]);
+pub async fn select_file(
+    current: PathBuf,
+    title: &str,
+    formats: &[ImageFormat],
+) -> Option<PathBuf> {
+    let mut dialog = AsyncFileDialog::new().set_title(title);
+
+    let all_images: Vec<&str> = formats
+        .iter()
+        .flat_map(|f| f.extensions())
+        .copied()
+        .collect();
+    if formats.len() > 1 {
+        dialog = dialog.add_filter("Firmware image", &all_images);
+    }
+    for format in formats {
+        dialog = dialog.add_filter(format.to_string(), format.extensions());
     }
 
     dialog = dialog.add_filter("All file", &["*"]);
@@ -26,3 +40,47 @@ pub async fn select_file(current: PathBuf, title: &str, allow_hex: bool) -> Opti
 
     dialog.pick_file().await.map(|h| h.path().to_path_buf())
 }
+
+pub async fn select_save_file(
+    title: &str,
+    file_name: &str,
+    filter_name: &str,
+    extension: &str,
+) -> Option<PathBuf> {
+    AsyncFileDialog::new()
+        .set_title(title)
+        .set_file_name(file_name)
+        .add_filter(filter_name, &[extension])
+        .save_file()
+        .await
+        .map(|h| h.path().to_path_buf())
+}
+
+/// Runs `f` on its own thread, so slow system calls do not stall the async
+/// executor shared with the UI.
+pub async fn unblock<T, F>(f: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (sender, receiver) = oneshot::channel();
+    thread::spawn(move || {
+        let _ = sender.send(f());
+    });
+
+    receiver.await.expect("the blocking thread panicked")
+}
+
+/// Shows a blocking Yes/No warning dialog. Returns `true` only if the user
+/// explicitly answered "Yes".
+pub async fn confirm(title: String, description: String) -> bool {
+    let result = AsyncMessageDialog::new()
+        .set_level(MessageLevel::Warning)
+        .set_title(title)
+        .set_description(description)
+        .set_buttons(MessageButtons::YesNo)
+        .show()
+        .await;
+
+    result == MessageDialogResult::Yes
+}